@@ -88,6 +88,7 @@ pub fn menu(props: &Props) -> Html {
                                 namespace: title.namespace.clone(),
                                 title: title.title.clone(),
                                 body: String::new(),
+                                properties: Default::default(),
                             })
                             .send()
                             .await
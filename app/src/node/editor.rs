@@ -117,6 +117,8 @@ pub fn editor(props: &Props) -> Html {
                         } else {
                             None
                         },
+                        properties: Default::default(),
+                        version: Some(node.version),
                     })
                     .send()
                     .await
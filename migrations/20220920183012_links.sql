@@ -0,0 +1,16 @@
+-- Links from a node's body to other nodes
+CREATE TABLE link (
+    -- The node the link is written in.
+    source_id INTEGER NOT NULL REFERENCES node(id) ON DELETE CASCADE,
+    -- The slug the link points to. A node with this slug may not exist.
+    target VARCHAR(256) NOT NULL,
+    -- The link text, as written in the body.
+    text TEXT NOT NULL,
+    -- The byte offset of the link in the body.
+    position INTEGER NOT NULL,
+
+    PRIMARY KEY (source_id, position)
+);
+
+-- Used to look up backlinks.
+CREATE INDEX link_target_idx ON link (target);
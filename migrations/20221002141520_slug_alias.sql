@@ -0,0 +1,10 @@
+-- Previous slugs of nodes, so links to them keep working after a rename
+CREATE TABLE slug_alias (
+    -- The previous slug. A node never has the same slug as an alias.
+    slug VARCHAR(256) PRIMARY KEY,
+    -- The node the slug now resolves to.
+    node_id INTEGER NOT NULL REFERENCES node(id) ON DELETE CASCADE
+);
+
+-- Used to look up the aliases of a node.
+CREATE INDEX slug_alias_node_id_idx ON slug_alias (node_id);
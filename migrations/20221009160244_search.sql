@@ -0,0 +1,7 @@
+-- Full-text search over nodes. Titles weigh more than bodies.
+ALTER TABLE node ADD COLUMN search TSVECTOR NOT NULL GENERATED ALWAYS AS (
+    setweight(to_tsvector('english', title), 'A') ||
+    setweight(to_tsvector('english', body), 'B')
+) STORED;
+
+CREATE INDEX node_search_idx ON node USING GIN (search);
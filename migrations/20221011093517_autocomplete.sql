@@ -0,0 +1,5 @@
+-- Trigram indexes, for suggesting nodes as their titles or slugs are typed.
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX node_title_trgm_idx ON node USING GIN (title gin_trgm_ops);
+CREATE INDEX node_slug_trgm_idx ON node USING GIN (slug gin_trgm_ops);
@@ -0,0 +1,17 @@
+-- Tags, labels that cut across namespaces
+CREATE TABLE tag (
+    id SERIAL PRIMARY KEY,
+    -- Normalized as by `ruinaio_model::tag::normalize`.
+    name VARCHAR(64) NOT NULL UNIQUE
+);
+
+-- The tags of each node
+CREATE TABLE node_tag (
+    node_id INTEGER NOT NULL REFERENCES node(id) ON DELETE CASCADE,
+    tag_id INTEGER NOT NULL REFERENCES tag(id) ON DELETE CASCADE,
+
+    PRIMARY KEY (node_id, tag_id)
+);
+
+-- Used to look up the nodes with a tag.
+CREATE INDEX node_tag_tag_id_idx ON node_tag (tag_id);
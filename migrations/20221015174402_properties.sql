@@ -0,0 +1,6 @@
+-- Structured metadata of nodes, like their status or owner
+ALTER TABLE node ADD COLUMN properties JSONB NOT NULL DEFAULT '{}'
+    CHECK (jsonb_typeof(properties) = 'object');
+
+-- Used to filter nodes by their properties.
+CREATE INDEX node_properties_idx ON node USING GIN (properties jsonb_path_ops);
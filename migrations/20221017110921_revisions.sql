@@ -0,0 +1,21 @@
+-- Every version of every node. There is no foreign key on purpose, so the
+-- history of a node outlives the node itself.
+CREATE TABLE node_revision (
+    node_id INTEGER NOT NULL,
+    -- Counts up from 1 for each node.
+    revision INTEGER NOT NULL,
+    slug VARCHAR(256) NOT NULL,
+    title VARCHAR(128) NOT NULL,
+    body TEXT NOT NULL,
+    properties JSONB NOT NULL,
+    -- Whether the node was deleted in this revision. The rest of the revision
+    -- is what the node looked like before it was deleted.
+    deleted BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+
+    PRIMARY KEY (node_id, revision)
+);
+
+-- Existing nodes start their history where they are now.
+INSERT INTO node_revision (node_id, revision, slug, title, body, properties)
+SELECT id, 1, slug, title, body, properties FROM node;
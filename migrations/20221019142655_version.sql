@@ -0,0 +1,6 @@
+-- Counts the writes to a node, so stale writes can be turned away.
+ALTER TABLE node ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+
+-- Revisions remember the version they were made at, so a node that is
+-- restored after being deleted can carry on counting from there.
+ALTER TABLE node_revision ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,607 @@
+//! Differences between texts.
+//!
+//! Texts are compared line by line with Myers' algorithm, and the changed
+//! lines are grouped into [`Hunk`]s with some unchanged lines around them for
+//! context, like `diff -u` does. Changed lines can also be compared word by
+//! word, to point out what changed within them.
+//!
+//! Texts too different to compare in reasonable time and memory are shown as
+//! entirely replaced, past what they have in common at their start and end.
+
+use serde::{Deserialize, Serialize};
+
+/// The differences between two revisions of a node.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct NodeDiff {
+    /// The revision compared from.
+    pub from: i32,
+    /// The revision compared to.
+    pub to: i32,
+    /// The change to the node's slug, if it changed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slug: Option<Change>,
+    /// The change to the node's title, if it changed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<Change>,
+    /// The changes to the node's body.
+    pub hunks: Vec<Hunk>,
+}
+
+impl NodeDiff {
+    /// Formats the diff as a unified diff, like [`unified`], with the slug
+    /// and title changes as header lines before it.
+    pub fn unified(&self, old_name: &str, new_name: &str) -> String {
+        let mut out = String::new();
+
+        for (name, change) in [("slug", &self.slug), ("title", &self.title)] {
+            if let Some(change) = change {
+                out.push_str(&format!("{} from {}\n", name, change.old));
+                out.push_str(&format!("{} to {}\n", name, change.new));
+            }
+        }
+
+        out.push_str(&unified(&self.hunks, old_name, new_name));
+        out
+    }
+}
+
+/// A value that was changed.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct Change {
+    pub old: String,
+    pub new: String,
+}
+
+impl Change {
+    /// Creates a change, or `None` if nothing changed.
+    pub fn new(old: &str, new: &str) -> Option<Change> {
+        if old == new {
+            None
+        } else {
+            Some(Change {
+                old: old.to_owned(),
+                new: new.to_owned(),
+            })
+        }
+    }
+}
+
+/// What happened to a line or word.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Op {
+    /// It is in both texts.
+    Equal,
+    /// It is only in the new text.
+    Insert,
+    /// It is only in the old text.
+    Delete,
+}
+
+/// A run of changed lines, along with the unchanged lines around them.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct Hunk {
+    /// The line the hunk starts at in the old text, counting from 1.
+    pub old_start: usize,
+    /// How many lines of the old text the hunk covers.
+    pub old_lines: usize,
+    /// The line the hunk starts at in the new text, counting from 1.
+    pub new_start: usize,
+    /// How many lines of the new text the hunk covers.
+    pub new_lines: usize,
+    /// The lines of the hunk.
+    pub lines: Vec<Line>,
+}
+
+/// A line of a [`Hunk`].
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct Line {
+    pub op: Op,
+    pub text: String,
+    /// What changed within the line, if words were compared.
+    ///
+    /// For deleted lines, these are the words that are kept or deleted. For
+    /// inserted lines, these are the words that are kept or inserted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub words: Option<Vec<Word>>,
+}
+
+/// A word of a changed [`Line`], or the whitespace or punctuation between
+/// words.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct Word {
+    pub op: Op,
+    pub text: String,
+}
+
+/// Compares two texts line by line.
+///
+/// Each hunk has up to `context` unchanged lines before and after its
+/// changes. If `words` is set, changed lines are also compared word by word.
+pub fn diff(old: &str, new: &str, context: usize, words: bool) -> Vec<Hunk> {
+    let old = old.lines().collect::<Vec<_>>();
+    let new = new.lines().collect::<Vec<_>>();
+
+    let ops = myers(&old, &new);
+
+    // find where each op is in both texts
+    let mut positions = Vec::with_capacity(ops.len());
+    let (mut x, mut y) = (0, 0);
+
+    for &op in ops.iter() {
+        positions.push((x, y));
+
+        match op {
+            Op::Equal => {
+                x += 1;
+                y += 1;
+            }
+            Op::Delete => x += 1,
+            Op::Insert => y += 1,
+        }
+    }
+
+    // group changes that are close enough to share their context
+    let mut ranges = Vec::<(usize, usize)>::new();
+
+    for (i, &op) in ops.iter().enumerate() {
+        if op == Op::Equal {
+            continue;
+        }
+
+        let start = i.saturating_sub(context);
+        let end = (i + context + 1).min(ops.len());
+
+        match ranges.last_mut() {
+            Some((_, last_end)) if *last_end >= start => *last_end = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    ranges
+        .into_iter()
+        .map(|(start, end)| {
+            let (old_start, new_start) = positions[start];
+
+            let mut lines = Vec::with_capacity(end - start);
+            let mut i = start;
+
+            while i < end {
+                if ops[i] == Op::Equal {
+                    let (x, _) = positions[i];
+                    lines.push(line(Op::Equal, old[x]));
+                    i += 1;
+                    continue;
+                }
+
+                // a block of changes is shown as its deletions, then its
+                // insertions
+                let mut deleted = Vec::new();
+                let mut inserted = Vec::new();
+
+                while i < end && ops[i] != Op::Equal {
+                    let (x, y) = positions[i];
+
+                    match ops[i] {
+                        Op::Delete => deleted.push(old[x]),
+                        Op::Insert => inserted.push(new[y]),
+                        Op::Equal => unreachable!(),
+                    }
+
+                    i += 1;
+                }
+
+                let mut deleted = deleted
+                    .into_iter()
+                    .map(|text| line(Op::Delete, text))
+                    .collect::<Vec<_>>();
+                let mut inserted = inserted
+                    .into_iter()
+                    .map(|text| line(Op::Insert, text))
+                    .collect::<Vec<_>>();
+
+                if words {
+                    // lines are paired off in order to compare their words
+                    for (old, new) in deleted.iter_mut().zip(inserted.iter_mut()) {
+                        let (old_words, new_words) = diff_words(&old.text, &new.text);
+                        old.words = Some(old_words);
+                        new.words = Some(new_words);
+                    }
+                }
+
+                lines.extend(deleted);
+                lines.extend(inserted);
+            }
+
+            let old_lines = lines.iter().filter(|line| line.op != Op::Insert).count();
+            let new_lines = lines.iter().filter(|line| line.op != Op::Delete).count();
+
+            Hunk {
+                // like `diff -u`, an empty range starts at the line before it
+                old_start: if old_lines > 0 { old_start + 1 } else { old_start },
+                old_lines,
+                new_start: if new_lines > 0 { new_start + 1 } else { new_start },
+                new_lines,
+                lines,
+            }
+        })
+        .collect()
+}
+
+/// Formats hunks as a unified diff, like `diff -u`.
+pub fn unified(hunks: &[Hunk], old_name: &str, new_name: &str) -> String {
+    let mut out = format!("--- {}\n+++ {}\n", old_name, new_name);
+
+    for hunk in hunks {
+        out.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            hunk.old_start, hunk.old_lines, hunk.new_start, hunk.new_lines,
+        ));
+
+        for line in hunk.lines.iter() {
+            let prefix = match line.op {
+                Op::Equal => ' ',
+                Op::Insert => '+',
+                Op::Delete => '-',
+            };
+
+            out.push(prefix);
+            out.push_str(&line.text);
+            out.push('\n');
+        }
+    }
+
+    out
+}
+
+fn line(op: Op, text: &str) -> Line {
+    Line {
+        op,
+        text: text.to_owned(),
+        words: None,
+    }
+}
+
+/// Compares two lines word by word, returning the words of each.
+fn diff_words(old: &str, new: &str) -> (Vec<Word>, Vec<Word>) {
+    let old = tokenize(old);
+    let new = tokenize(new);
+
+    let mut old_words = Vec::<Word>::new();
+    let mut new_words = Vec::<Word>::new();
+    let (mut x, mut y) = (0, 0);
+
+    for op in myers(&old, &new) {
+        match op {
+            Op::Equal => {
+                push_word(&mut old_words, op, old[x]);
+                push_word(&mut new_words, op, new[y]);
+                x += 1;
+                y += 1;
+            }
+            Op::Delete => {
+                push_word(&mut old_words, op, old[x]);
+                x += 1;
+            }
+            Op::Insert => {
+                push_word(&mut new_words, op, new[y]);
+                y += 1;
+            }
+        }
+    }
+
+    (old_words, new_words)
+}
+
+/// Pushes a word, merging it into the last word if they have the same op.
+fn push_word(words: &mut Vec<Word>, op: Op, text: &str) {
+    match words.last_mut() {
+        Some(last) if last.op == op => last.text.push_str(text),
+        _ => words.push(Word { op, text: text.to_owned() }),
+    }
+}
+
+/// Splits a line into words, runs of whitespace and single punctuation
+/// marks.
+fn tokenize(s: &str) -> Vec<&str> {
+    #[derive(PartialEq)]
+    enum Class {
+        Word,
+        Space,
+        Other,
+    }
+
+    fn class(c: char) -> Class {
+        if c.is_alphanumeric() || c == '_' {
+            Class::Word
+        } else if c.is_whitespace() {
+            Class::Space
+        } else {
+            Class::Other
+        }
+    }
+
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut last = None;
+
+    for (i, c) in s.char_indices() {
+        let class = class(c);
+
+        if i > start && (last.as_ref() != Some(&class) || class == Class::Other) {
+            tokens.push(&s[start..i]);
+            start = i;
+        }
+
+        last = Some(class);
+    }
+
+    if start < s.len() {
+        tokens.push(&s[start..]);
+    }
+
+    tokens
+}
+
+/// The most edits [`myers`] looks for before it gives up.
+///
+/// The trace kept to walk back through the search grows with the square of
+/// the edits, so this keeps it to a few megabytes.
+const MAX_EDITS: usize = 1000;
+
+/// Finds the shortest edit script between two sequences, with Myers'
+/// algorithm.
+///
+/// If the sequences are more than [`MAX_EDITS`] edits apart, everything
+/// between their common start and end is replaced instead.
+fn myers<T: PartialEq>(a: &[T], b: &[T]) -> Vec<Op> {
+    // the common start and end don't need to be searched
+    let prefix = a.iter().zip(b).take_while(|(a, b)| a == b).count();
+    let (a, b) = (&a[prefix..], &b[prefix..]);
+    let suffix = a.iter().rev().zip(b.iter().rev()).take_while(|(a, b)| a == b).count();
+    let (a, b) = (&a[..a.len() - suffix], &b[..b.len() - suffix]);
+
+    let mut ops = vec![Op::Equal; prefix];
+
+    match shortest_edit(a, b) {
+        Some(edit) => ops.extend(edit),
+        None => {
+            ops.resize(ops.len() + a.len(), Op::Delete);
+            ops.resize(ops.len() + b.len(), Op::Insert);
+        }
+    }
+
+    ops.resize(ops.len() + suffix, Op::Equal);
+    ops
+}
+
+/// Searches for the shortest edit script, or `None` if it has more than
+/// [`MAX_EDITS`] edits.
+fn shortest_edit<T: PartialEq>(a: &[T], b: &[T]) -> Option<Vec<Op>> {
+    let n = a.len() as isize;
+    let m = b.len() as isize;
+    let max = (n + m).min(MAX_EDITS as isize);
+
+    // the furthest x reached on each diagonal k = x - y, offset by `max + 1`
+    // so the diagonals next to the outermost ones can be read
+    let index = |k: isize| (k + max + 1) as usize;
+    let mut v = vec![0isize; 2 * max as usize + 3];
+
+    // the diagonals -d - 1 through d + 1 of `v` before each step d, which are
+    // all that step can read
+    let mut trace = Vec::new();
+    let mut found = false;
+
+    'search: for d in 0..=max {
+        trace.push(v[index(-d - 1)..=index(d + 1)].to_vec());
+
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && v[index(k - 1)] < v[index(k + 1)]) {
+                v[index(k + 1)]
+            } else {
+                v[index(k - 1)] + 1
+            };
+            let mut y = x - k;
+
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+
+            v[index(k)] = x;
+
+            if x >= n && y >= m {
+                found = true;
+                break 'search;
+            }
+        }
+    }
+
+    if !found {
+        return None;
+    }
+
+    // walk back through the furthest reaching paths
+    let mut ops = Vec::new();
+    let (mut x, mut y) = (n, m);
+
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let at = |k: isize| v[(k + d + 1) as usize];
+
+        let prev_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = at(prev_k);
+        let prev_y = prev_x - prev_k;
+
+        while x > prev_x && y > prev_y {
+            ops.push(Op::Equal);
+            x -= 1;
+            y -= 1;
+        }
+
+        if d > 0 {
+            if x == prev_x {
+                ops.push(Op::Insert);
+            } else {
+                ops.push(Op::Delete);
+            }
+        }
+
+        x = prev_x;
+        y = prev_y;
+    }
+
+    ops.reverse();
+    Some(ops)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_myers() {
+        let a = "ABCABBA".chars().collect::<Vec<_>>();
+        let b = "CBABAC".chars().collect::<Vec<_>>();
+
+        let ops = myers(&a, &b);
+
+        // the shortest edit script of the classic example has 5 edits
+        assert_eq!(ops.iter().filter(|&&op| op != Op::Equal).count(), 5);
+
+        // and applying it to `a` gives `b`
+        let mut x = 0;
+        let mut y = 0;
+        let mut out = Vec::new();
+
+        for op in ops {
+            match op {
+                Op::Equal => {
+                    out.push(a[x]);
+                    x += 1;
+                    y += 1;
+                }
+                Op::Delete => x += 1,
+                Op::Insert => {
+                    out.push(b[y]);
+                    y += 1;
+                }
+            }
+        }
+
+        assert_eq!(out, b);
+
+        assert!(myers::<char>(&[], &[]).is_empty());
+        assert_eq!(myers(&[], &['a']), [Op::Insert]);
+        assert_eq!(myers(&['a'], &[]), [Op::Delete]);
+    }
+
+    #[test]
+    fn test_myers_max_edits() {
+        // every line is different, so the search gives up
+        let a = (0..MAX_EDITS).map(|i| i * 2).collect::<Vec<_>>();
+        let b = (0..MAX_EDITS).map(|i| i * 2 + 1).collect::<Vec<_>>();
+
+        let mut a = [vec![0], a, vec![0]].concat();
+        let b = [vec![0], b, vec![0]].concat();
+
+        let ops = myers(&a, &b);
+
+        // the common start and end are kept, and the rest is replaced
+        assert_eq!(ops.len(), 2 * MAX_EDITS + 2);
+        assert_eq!(ops[0], Op::Equal);
+        assert!(ops[1..=MAX_EDITS].iter().all(|&op| op == Op::Delete));
+        assert!(ops[MAX_EDITS + 1..=2 * MAX_EDITS].iter().all(|&op| op == Op::Insert));
+        assert_eq!(ops[2 * MAX_EDITS + 1], Op::Equal);
+
+        // but a few edits in long sequences are still found
+        a[MAX_EDITS / 2] = 1;
+        let ops = myers(&a, &[vec![0], (0..MAX_EDITS).map(|i| i * 2).collect(), vec![0]].concat());
+        assert_eq!(ops.iter().filter(|&&op| op != Op::Equal).count(), 2);
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = "one\ntwo\nthree\nfour\nfive\nsix\nseven\neight\nnine\nten";
+        let new = "one\ntwo\nthree\nFOUR\nfive\nsix\nseven\neight\nnine\nten\neleven";
+
+        let hunks = diff(old, new, 1, false);
+
+        assert_eq!(hunks.len(), 2);
+        assert_eq!((hunks[0].old_start, hunks[0].old_lines), (3, 3));
+        assert_eq!((hunks[0].new_start, hunks[0].new_lines), (3, 3));
+        assert_eq!((hunks[1].old_start, hunks[1].old_lines), (10, 1));
+        assert_eq!((hunks[1].new_start, hunks[1].new_lines), (10, 2));
+
+        assert_eq!(unified(&hunks, "a", "b"), "\
+--- a
++++ b
+@@ -3,3 +3,3 @@
+ three
+-four
++FOUR
+ five
+@@ -10,1 +10,2 @@
+ ten
++eleven
+");
+
+        // close changes share their context
+        assert_eq!(diff(old, new, 3, false).len(), 1);
+
+        // nothing changed, nothing to show
+        assert!(diff(old, old, 3, false).is_empty());
+
+        // changes besides the body come first
+        let node = NodeDiff {
+            from: 1,
+            to: 2,
+            slug: None,
+            title: Change::new("Piggie", "Princess Piggie"),
+            hunks: Vec::new(),
+        };
+
+        assert_eq!(node.unified("a", "b"), "\
+title from Piggie
+title to Princess Piggie
+--- a
++++ b
+");
+
+        // empty ranges start at the line before them
+        let hunks = diff("", "new", 3, false);
+        assert_eq!((hunks[0].old_start, hunks[0].old_lines), (0, 0));
+        assert_eq!((hunks[0].new_start, hunks[0].new_lines), (1, 1));
+    }
+
+    #[test]
+    fn test_diff_words() {
+        let hunks = diff("Meet the princess.", "Meet the pig princess!", 0, true);
+        let lines = &hunks[0].lines;
+
+        fn words(line: &Line) -> Vec<(Op, &str)> {
+            line.words
+                .as_ref()
+                .unwrap()
+                .iter()
+                .map(|word| (word.op, word.text.as_str()))
+                .collect()
+        }
+
+        assert_eq!(words(&lines[0]), [
+            (Op::Equal, "Meet the princess"),
+            (Op::Delete, "."),
+        ]);
+        assert_eq!(words(&lines[1]), [
+            (Op::Equal, "Meet the "),
+            (Op::Insert, "pig "),
+            (Op::Equal, "princess"),
+            (Op::Insert, "!"),
+        ]);
+    }
+}
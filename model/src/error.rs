@@ -12,22 +12,87 @@ pub struct Error {
     pub code: Code,
     /// A more human-readable reason as to why the error occured.
     pub reason: String,
+    /// Identifies an internal error in the server's logs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Every invalid field of the request, if the error was caused by them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldError>,
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        write!(f, "#{}: {}", self.code, self.reason)
+        write!(f, "#{}: {}", self.code, self.reason)?;
+
+        match &self.id {
+            Some(id) => write!(f, " ({})", id),
+            None => Ok(()),
+        }
     }
 }
 
 impl std::error::Error for Error { }
 
+/// A problem with a single field of a request.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct FieldError {
+    /// A JSON pointer to the field, like `/title` or `/properties/color`.
+    pub pointer: String,
+    /// The code describing the problem.
+    pub code: Code,
+    /// A human-readable description of the problem.
+    pub message: String,
+    /// The character that made a slug invalid.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub char: Option<char>,
+    /// The column of [`FieldError::char`], starting at 1.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub column: Option<usize>,
+}
+
+impl FieldError {
+    /// Creates a new `FieldError`.
+    pub fn new<P, S>(pointer: P, code: Code, message: S) -> FieldError
+    where
+        P: Into<String>,
+        S: Into<String>,
+    {
+        FieldError {
+            pointer: pointer.into(),
+            code,
+            message: message.into(),
+            char: None,
+            column: None,
+        }
+    }
+
+    /// Creates a `FieldError` for an invalid slug.
+    pub fn slug<P>(pointer: P, error: &crate::slug::Error) -> FieldError
+    where
+        P: Into<String>,
+    {
+        let mut field = FieldError::new(pointer, Code::InvalidSlug, error.to_string());
+
+        if let crate::slug::Error::InvalidChar(ch, col) = *error {
+            field.char = Some(ch);
+            field.column = Some(col + 1);
+        }
+
+        field
+    }
+}
+
+/// Escapes a key so it can be used as a single token of a JSON pointer.
+pub fn pointer_token(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
 /// A unique identifier for an [`Error`].
 #[repr(u32)]
-#[derive(Clone, Copy, Debug, Deserialize_repr, Hash, Serialize_repr)]
+#[derive(Clone, Copy, Debug, Deserialize_repr, Eq, Hash, PartialEq, Serialize_repr)]
 pub enum Code {
-    /// An internal server error occured. The message will contain basic debug
-    /// information.
+    /// An internal server error occured. The error's `id` can be used to find
+    /// it in the server's logs.
     InternalServerError = 21,
     /// The object was not found.
     NotFound = 2004,
@@ -35,6 +100,30 @@ pub enum Code {
     OutOfBounds = 4001,
     /// A slug was malformed or invalid.
     InvalidSlug = 4002,
+    /// A relation would make a node its own ancestor.
+    CyclicRelation = 4003,
+    /// An image with the same name or contents already exists.
+    DuplicateImage = 4004,
+    /// The request body is larger than allowed.
+    PayloadTooLarge = 4005,
+    /// An upload was malformed or had no file in it.
+    InvalidUpload = 4006,
+    /// A requested byte range lies outside of the resource.
+    RangeNotSatisfiable = 4007,
+    /// A node with the same slug already exists.
+    SlugConflict = 4008,
+    /// The `If-Match` header of a write did not match the node's `ETag`.
+    PreconditionFailed = 4009,
+    /// A write was made against an older version of the node.
+    VersionConflict = 4010,
+    /// The request body was not valid JSON, or did not fit the endpoint.
+    InvalidJson = 4011,
+    /// The query string was malformed or did not fit the endpoint.
+    InvalidQuery = 4012,
+    /// A parameter in the path was malformed, like an id that is not a number.
+    InvalidPath = 4013,
+    /// The request body was sent with a content type other than JSON.
+    UnsupportedMediaType = 4014,
 }
 
 impl Display for Code {
@@ -0,0 +1,29 @@
+//! Images attached to nodes.
+
+use serde::{Deserialize, Serialize};
+
+/// An image uploaded to a node.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Image {
+    /// The unique identifier of the node the image belongs to.
+    pub node_id: i32,
+    /// The image's filename, unique within its node.
+    pub filename: String,
+    /// The SHA-256 hash of the image's contents, in lowercase hex.
+    pub hash: String,
+    /// A URL to the image, ready to be put into Markdown.
+    pub url: String,
+}
+
+/// The results of collecting blobs no image refers to anymore.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct GcReport {
+    /// Whether this was a dry run, in which case nothing was deleted.
+    pub dry_run: bool,
+    /// How many blobs were looked at.
+    pub scanned: u64,
+    /// How many blobs were (or would have been) deleted.
+    pub collected: u64,
+    /// How many bytes were (or would have been) reclaimed.
+    pub bytes_reclaimed: u64,
+}
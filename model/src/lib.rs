@@ -1,9 +1,17 @@
 //! Ruina's data model.
 
+pub mod diff;
 pub mod error;
+pub mod image;
+pub mod link;
+pub mod namespace;
 pub mod node;
+pub mod page;
 pub mod params;
+pub mod revision;
+pub mod search;
 pub mod slug;
+pub mod tag;
 mod patch;
 
 pub use node::Node;
@@ -0,0 +1,100 @@
+//! Links between nodes.
+//!
+//! Nodes link to each other with Markdown shortcut references, like
+//! `[Princess Piggie]`. A reference without a matching link definition points
+//! to the node whose slug is the slugified reference, as with
+//! [`slug::slugify_path`](crate::slug::slugify_path), or whose slug it was
+//! before the node was renamed.
+
+use serde::{Deserialize, Serialize};
+
+use pulldown_cmark::{BrokenLink, LinkType, Options, Parser};
+
+use crate::node::NodeSummary;
+use crate::slug;
+
+/// A link to another node in a node's body.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Link {
+    /// The slug of the node the link points to.
+    pub target: String,
+    /// The text of the link, as written in the body.
+    pub text: String,
+    /// The byte offset of the link in the body.
+    pub position: usize,
+}
+
+/// A link to another node, along with the node it resolves to.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ResolvedLink {
+    /// The link itself.
+    #[serde(flatten)]
+    pub link: Link,
+    /// The node the link resolves to, if it exists.
+    pub node: Option<NodeSummary>,
+}
+
+/// A node with links that resolve to no existing node.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct DanglingLinks {
+    /// The node the links are in.
+    #[serde(flatten)]
+    pub node: NodeSummary,
+    /// The links that resolve to no existing node, in order.
+    pub links: Vec<Link>,
+}
+
+/// Finds all links to other nodes in a Markdown body.
+///
+/// References that cannot be turned into a slug are skipped.
+pub fn extract(body: &str) -> Vec<Link> {
+    let mut links = Vec::new();
+
+    let mut callback = |link: BrokenLink| {
+        if link.link_type == LinkType::Shortcut {
+            if let Ok(target) = slug::slugify_path(&link.reference) {
+                links.push(Link {
+                    target: target.into_owned(),
+                    text: link.reference.to_string(),
+                    position: link.span.start,
+                });
+            }
+        }
+
+        None
+    };
+
+    // the callback is only called as the body is parsed
+    Parser::new_with_broken_link_callback(
+        body,
+        Options::all() & !Options::ENABLE_HEADING_ATTRIBUTES,
+        Some(&mut callback),
+    )
+        .for_each(drop);
+
+    links
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_extract() {
+        use super::extract;
+
+        let links = extract("Meet [Princess Piggie] of [Lore/The West].");
+        let links = links
+            .iter()
+            .map(|link| (link.target.as_str(), link.text.as_str(), link.position))
+            .collect::<Vec<_>>();
+
+        assert_eq!(links, [
+            ("PrincessPiggie", "Princess Piggie", 5),
+            ("Lore/TheWest", "Lore/The West", 26),
+        ]);
+
+        // defined references, inline links and code are not node links
+        assert!(extract("[Defined]\n\n[Defined]: https://example.com").is_empty());
+        assert!(extract("[Inline](https://example.com)").is_empty());
+        assert!(extract("`[Code]`").is_empty());
+    }
+}
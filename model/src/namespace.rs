@@ -0,0 +1,39 @@
+//! Namespaces.
+//!
+//! A namespace is every slug prefix ending in a slash, so `Lore/` and
+//! `Lore/Characters/` are both namespaces of `Lore/Characters/PrincessPiggie`.
+
+use serde::{Deserialize, Serialize};
+
+/// A namespace along with its sub-namespaces.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Namespace {
+    /// The full prefix of the namespace, like `Lore/Characters/`.
+    pub prefix: String,
+    /// How many nodes are directly in the namespace.
+    pub nodes: u64,
+    /// How many nodes are in the namespace and all of its sub-namespaces.
+    pub total_nodes: u64,
+    /// The direct sub-namespaces of the namespace.
+    pub children: Vec<Namespace>,
+}
+
+impl Namespace {
+    /// Gets the last segment of the namespace, without a trailing slash.
+    pub fn name(&self) -> &str {
+        let prefix = self.prefix.trim_end_matches('/');
+        let (_, name) = crate::slug::split(prefix);
+        name
+    }
+}
+
+/// A node that was moved to another namespace.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct MovedNode {
+    /// The unique identifier of the node.
+    pub id: i32,
+    /// The slug of the node before it was moved.
+    pub old_slug: String,
+    /// The slug of the node after it was moved.
+    pub slug: String,
+}
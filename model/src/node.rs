@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 
 /// A single node.
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
@@ -11,6 +12,15 @@ pub struct Node {
     pub title: String,
     /// The actual content of the node.
     pub body: String,
+    /// Counts up every time the node is written to.
+    #[serde(default)]
+    pub version: i32,
+    /// The node's tags, in order, if they were fetched with the node.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+    /// Structured metadata of the node, like its status or owner.
+    #[serde(default)]
+    pub properties: Map<String, Value>,
 }
 
 impl Node {
@@ -21,3 +31,74 @@ impl Node {
     }
 }
 
+
+/// A node looked up by its slug.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct SlugMatch {
+    /// The node itself.
+    #[serde(flatten)]
+    pub node: Node,
+    /// The previous slug of the node that was looked up, if the node has
+    /// been renamed since.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub redirected_from: Option<String>,
+}
+
+/// A lightweight reference to a node, without its body.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct NodeSummary {
+    /// The unique identifier of the node.
+    pub id: i32,
+    /// The node's unique slug.
+    pub slug: String,
+    /// The node's title.
+    pub title: String,
+}
+
+impl NodeSummary {
+    /// Gets the namespace the node is in.
+    pub fn namespace(&self) -> Option<&str> {
+        let (namespace, _) = crate::slug::split(&self.slug);
+        namespace
+    }
+}
+
+impl From<Node> for NodeSummary {
+    fn from(node: Node) -> NodeSummary {
+        NodeSummary {
+            id: node.id,
+            slug: node.slug,
+            title: node.title,
+        }
+    }
+}
+
+/// A node along with its direct parents and children.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct NodeWithRelations {
+    /// The node itself.
+    #[serde(flatten)]
+    pub node: Node,
+    /// The node's direct parents.
+    pub parents: Vec<NodeSummary>,
+    /// The node's direct children.
+    pub children: Vec<NodeSummary>,
+}
+
+/// A node reached while walking relations, along with the nodes reached
+/// through it.
+///
+/// When walking descendants, `nodes` are the node's children; when walking
+/// ancestors, they are its parents. A node reachable through several paths
+/// appears once, under the parent closest to where the walk started, the one
+/// with the lowest id if there are several.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct NodeTree {
+    /// The node itself.
+    #[serde(flatten)]
+    pub node: Node,
+    /// How many relations away the node is from where the walk started.
+    pub depth: u32,
+    /// The nodes reached through this node.
+    pub nodes: Vec<NodeTree>,
+}
@@ -0,0 +1,15 @@
+//! Pages of listings.
+
+use serde::{Deserialize, Serialize};
+
+/// A page of a listing paged with cursors.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Page<T> {
+    /// The items on this page.
+    pub items: Vec<T>,
+    /// The cursor of the next page, or `None` if this is the last page.
+    pub next_cursor: Option<String>,
+    /// The total amount of items across all pages, if it was asked for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
+}
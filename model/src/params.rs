@@ -1,6 +1,12 @@
 //! API parameters.
 
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use std::collections::HashMap;
+
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
 
 use crate::Patch;
 
@@ -10,6 +16,38 @@ use crate::Patch;
 pub struct ListNodes {
     pub page: u32,
     pub limit: u32,
+    /// Only lists nodes in this namespace, like `Lore/`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    /// Whether nodes in sub-namespaces of `namespace` are listed too.
+    pub recursive: bool,
+    /// Only lists nodes with titles containing this, ignoring case.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Only lists nodes with these tags, separated by commas.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<String>,
+    /// Whether nodes need all of `tags` or any of them, see [`TagMode`].
+    pub tag_mode: String,
+    /// Only lists nodes with these properties, as a JSON object.
+    ///
+    /// Each property has to be equal to the node's, nested objects and arrays
+    /// included.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub properties: Option<String>,
+    /// What to sort the nodes by, see [`SortKey`].
+    pub sort: String,
+    /// Which direction to sort the nodes in, see [`SortOrder`].
+    pub order: String,
+    /// Pages with cursors instead of page numbers if present.
+    ///
+    /// An empty cursor gets the first page. Later pages are gotten with the
+    /// `next_cursor` of the page before, which also carries `sort` and
+    /// `order`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+    /// Whether to count the nodes across all pages, when paging with cursors.
+    pub total: bool,
 }
 
 impl Default for ListNodes {
@@ -17,6 +55,247 @@ impl Default for ListNodes {
         ListNodes {
             page: 1,
             limit: 20,
+            namespace: None,
+            recursive: true,
+            title: None,
+            tags: None,
+            tag_mode: TagMode::All.to_string(),
+            properties: None,
+            sort: SortKey::Id.to_string(),
+            order: SortOrder::Asc.to_string(),
+            cursor: None,
+            total: false,
+        }
+    }
+}
+
+/// What nodes can be sorted by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortKey {
+    Id,
+    Slug,
+    Title,
+}
+
+impl SortKey {
+    /// All of the sort keys.
+    pub const ALL: &'static [SortKey] = &[SortKey::Id, SortKey::Slug, SortKey::Title];
+
+    /// Gets the name of the sort key, as used in [`ListNodes::sort`].
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SortKey::Id => "id",
+            SortKey::Slug => "slug",
+            SortKey::Title => "title",
+        }
+    }
+}
+
+impl Display for SortKey {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SortKey {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<SortKey, ()> {
+        SortKey::ALL
+            .iter()
+            .copied()
+            .find(|key| key.as_str() == s)
+            .ok_or(())
+    }
+}
+
+/// Which direction nodes are sorted in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    /// Gets the name of the order, as used in [`ListNodes::order`].
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SortOrder::Asc => "asc",
+            SortOrder::Desc => "desc",
+        }
+    }
+}
+
+impl Display for SortOrder {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SortOrder {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<SortOrder, ()> {
+        match s {
+            "asc" => Ok(SortOrder::Asc),
+            "desc" => Ok(SortOrder::Desc),
+            _ => Err(()),
+        }
+    }
+}
+
+/// How the `tags` of a [`ListNodes`] are matched.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TagMode {
+    /// Nodes need every tag.
+    All,
+    /// Nodes need at least one of the tags.
+    Any,
+}
+
+impl TagMode {
+    /// Gets the name of the mode, as used in [`ListNodes::tag_mode`].
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TagMode::All => "all",
+            TagMode::Any => "any",
+        }
+    }
+}
+
+impl Display for TagMode {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for TagMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<TagMode, ()> {
+        match s {
+            "all" => Ok(TagMode::All),
+            "any" => Ok(TagMode::Any),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Request query parameters for `GET /node/{node.id}/ancestors` and
+/// `GET /node/{node.id}/descendants`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Traverse {
+    /// How many relations deep to walk.
+    pub depth: u32,
+    /// Whether to return a tree of nodes instead of a flat list.
+    pub nested: bool,
+}
+
+impl Default for Traverse {
+    fn default() -> Traverse {
+        Traverse {
+            depth: 8,
+            nested: false,
+        }
+    }
+}
+
+/// Request query parameters for `GET /node/{node.id}/revisions`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ListRevisions {
+    pub page: u32,
+    pub limit: u32,
+}
+
+impl Default for ListRevisions {
+    fn default() -> ListRevisions {
+        ListRevisions {
+            page: 1,
+            limit: 20,
+        }
+    }
+}
+
+/// Request query parameters for `GET /node/{node.id}/diff`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Diff {
+    /// The revision to compare from. Defaults to the one before `to`.
+    ///
+    /// Revision 0 is the empty node before the first revision.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<i32>,
+    /// The revision to compare to. Defaults to the latest revision.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<i32>,
+    /// Either `json` for structured hunks, or `unified` for a unified diff.
+    pub format: String,
+    /// Whether changed lines are also compared word by word.
+    pub words: bool,
+    /// How many unchanged lines to show around changes.
+    pub context: u32,
+}
+
+impl Default for Diff {
+    fn default() -> Diff {
+        Diff {
+            from: None,
+            to: None,
+            format: String::from("json"),
+            words: false,
+            context: 3,
+        }
+    }
+}
+
+/// Request query parameters for `POST /admin/gc`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CollectGarbage {
+    /// Only report what would be collected, without deleting anything.
+    pub dry_run: bool,
+}
+
+/// Request query parameters for `GET /search`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Search {
+    /// The search query, see [`search`](crate::search) for its syntax.
+    pub q: String,
+    /// Only searches nodes in this namespace, like `Lore/`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    pub page: u32,
+    pub limit: u32,
+}
+
+impl Default for Search {
+    fn default() -> Search {
+        Search {
+            q: String::new(),
+            namespace: None,
+            page: 1,
+            limit: 20,
+        }
+    }
+}
+
+/// Request query parameters for `GET /autocomplete`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Autocomplete {
+    /// The title or slug typed so far.
+    pub q: String,
+    pub limit: u32,
+}
+
+impl Default for Autocomplete {
+    fn default() -> Autocomplete {
+        Autocomplete {
+            q: String::new(),
+            limit: 10,
         }
     }
 }
@@ -30,6 +309,13 @@ pub struct UpdateNode {
     pub title: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub body: Option<String>,
+    /// Properties to change, by key. A `null` value removes the property.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub properties: HashMap<String, Patch<Value>>,
+    /// The version of the node the update was made against. If the node has
+    /// been written to since, the update is turned away.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<i32>,
 }
 
 /// Request body parameters for `POST /node/new`.
@@ -38,5 +324,25 @@ pub struct CreateNode {
     pub namespace: Option<String>,
     pub title: String,
     pub body: String,
+    #[serde(default)]
+    pub properties: Map<String, Value>,
 }
 
+
+/// Request body parameters for `POST /namespaces/move`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct MoveNamespace {
+    /// The namespace to move, like `Drafts/`.
+    pub from: String,
+    /// The namespace to move it to, like `Lore/Archive/`. If empty, the
+    /// namespace is moved to the top level.
+    pub to: String,
+}
+
+/// Request body parameters for `PUT /node/{node.id}/tags`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SetTags {
+    /// Every tag the node should have. Tags the node has that are not listed
+    /// are removed.
+    pub tags: Vec<String>,
+}
@@ -0,0 +1,48 @@
+//! Revisions of nodes.
+//!
+//! Every change to a node is kept as a new revision, numbered from 1 up.
+//! Restoring an old revision writes it out as a new one, so history is never
+//! rewritten.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use time::OffsetDateTime;
+
+/// A node as it was at some point in time.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Revision {
+    /// The unique identifier of the node.
+    pub node_id: i32,
+    /// The number of the revision.
+    pub revision: i32,
+    /// The node's slug at the time.
+    pub slug: String,
+    /// The node's title at the time.
+    pub title: String,
+    /// The node's body at the time.
+    pub body: String,
+    /// The node's properties at the time.
+    pub properties: Map<String, Value>,
+    /// Whether the node was deleted in this revision.
+    pub deleted: bool,
+    /// When the revision was made.
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+/// A revision of a node, without its contents.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct RevisionSummary {
+    /// The number of the revision.
+    pub revision: i32,
+    /// The node's slug at the time.
+    pub slug: String,
+    /// The node's title at the time.
+    pub title: String,
+    /// Whether the node was deleted in this revision.
+    pub deleted: bool,
+    /// When the revision was made.
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
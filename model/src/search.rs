@@ -0,0 +1,149 @@
+//! Full-text search over nodes.
+//!
+//! Besides searching, nodes can be suggested as their title or slug is
+//! typed, see [`Suggestion`].
+//!
+//! Search queries are a list of terms, all of which have to match:
+//!
+//! * `piggie` matches nodes with words like "piggie" in them.
+//! * `pig*` matches nodes with words starting with "pig".
+//! * `"princess piggie"` matches the words right after each other.
+//! * `-castle` matches nodes without words like "castle" in them, and also
+//!   works on phrases.
+
+use serde::{Deserialize, Serialize};
+
+use crate::node::NodeSummary;
+
+/// A node matching a search.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct SearchResult {
+    /// The node that matched.
+    #[serde(flatten)]
+    pub node: NodeSummary,
+    /// How well the node matched. Higher is better.
+    pub rank: f32,
+    /// Fragments of the node's body as HTML, with matches surrounded by
+    /// `<mark>` and `</mark>`.
+    ///
+    /// The rest of the snippet is HTML-escaped, so it can be displayed as-is.
+    pub snippet: String,
+}
+
+/// A node suggested for a partially typed title or slug.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Suggestion {
+    /// The node suggested.
+    #[serde(flatten)]
+    pub node: NodeSummary,
+    /// The namespace the node is in, like `Lore/`.
+    pub namespace: Option<String>,
+}
+
+impl From<NodeSummary> for Suggestion {
+    fn from(node: NodeSummary) -> Suggestion {
+        Suggestion {
+            namespace: node.namespace().map(ToOwned::to_owned),
+            node,
+        }
+    }
+}
+
+/// Converts a search query into a Postgres `tsquery`, to be given to
+/// `to_tsquery`.
+///
+/// Returns `None` if the query has no terms.
+pub fn to_tsquery(query: &str) -> Option<String> {
+    let mut terms = Vec::new();
+    let mut chars = query.chars().peekable();
+
+    loop {
+        // skip to the next term
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+
+        let c = match chars.peek() {
+            Some(&c) => c,
+            None => break,
+        };
+
+        let negated = c == '-';
+        if negated {
+            chars.next();
+        }
+
+        let term = if chars.next_if_eq(&'"').is_some() {
+            let phrase = chars.by_ref().take_while(|&c| c != '"').collect::<String>();
+            let words = phrase
+                .split_whitespace()
+                .filter_map(lexeme)
+                .collect::<Vec<_>>();
+
+            match words.len() {
+                0 => None,
+                1 => words.into_iter().next(),
+                _ => Some(format!("({})", words.join(" <-> "))),
+            }
+        } else {
+            let word = chars.by_ref().take_while(|c| !c.is_whitespace()).collect::<String>();
+            lexeme(&word)
+        };
+
+        if let Some(term) = term {
+            if negated {
+                terms.push(format!("!{}", term));
+            } else {
+                terms.push(term);
+            }
+        }
+    }
+
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" & "))
+    }
+}
+
+/// Quotes a single word of a query, so operators in it are taken literally.
+fn lexeme(word: &str) -> Option<String> {
+    let (word, prefix) = match word.trim_end_matches('*') {
+        trimmed if trimmed.len() < word.len() => (trimmed, true),
+        _ => (word, false),
+    };
+
+    if word.is_empty() {
+        return None;
+    }
+
+    let quoted = word.replace('\\', "\\\\").replace('\'', "''");
+
+    if prefix {
+        Some(format!("'{}':*", quoted))
+    } else {
+        Some(format!("'{}'", quoted))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_to_tsquery() {
+        use super::to_tsquery;
+
+        assert_eq!(to_tsquery("princess piggie").as_deref(), Some("'princess' & 'piggie'"));
+        assert_eq!(to_tsquery("pig*").as_deref(), Some("'pig':*"));
+        assert_eq!(
+            to_tsquery("\"princess piggie\" -castle").as_deref(),
+            Some("('princess' <-> 'piggie') & !'castle'"),
+        );
+        assert_eq!(to_tsquery("-\"the west\"").as_deref(), Some("!('the' <-> 'west')"));
+
+        // operators and quotes are taken literally
+        assert_eq!(to_tsquery("don't & <->").as_deref(), Some("'don''t' & '&' & '<->'"));
+
+        // unclosed phrases run to the end
+        assert_eq!(to_tsquery("\"princess pig*").as_deref(), Some("('princess' <-> 'pig':*)"));
+
+        assert_eq!(to_tsquery("  * - \"\" "), None);
+    }
+}
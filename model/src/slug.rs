@@ -83,6 +83,28 @@ pub fn slugify<'a>(s: &'a str) -> Result<Cow<'a, str>, Error> {
     }
 }
 
+/// Turns a generic path, like `Lore/Characters/Princess Piggie`, into a slug.
+///
+/// Each segment between slashes is slugified on its own with [`slugify`], so
+/// the namespaces of the path are kept.
+pub fn slugify_path<'a>(s: &'a str) -> Result<Cow<'a, str>, Error> {
+    if !s.contains('/') {
+        return slugify(s);
+    }
+
+    let mut result = String::with_capacity(s.len());
+
+    for segment in s.split('/') {
+        if !result.is_empty() {
+            result.push('/');
+        }
+
+        result.push_str(&slugify(segment)?);
+    }
+
+    Ok(Cow::Owned(result))
+}
+
 fn is_valid(ch: char) -> bool {
     ch.is_ascii_alphanumeric() || ch == '/'
 }
@@ -155,5 +177,17 @@ mod tests {
 
         assert_eq!(slugify("The quick brown fox, jumped over the lazy dog.").unwrap(), "TheQuickBrownFoxJumpedOverTheLazyDog");
     }
+
+    #[test]
+    fn test_slugify_path() {
+        use super::slugify_path;
+
+        assert_eq!(slugify_path("Princess Piggie").unwrap(), "PrincessPiggie");
+        assert_eq!(slugify_path("Lore/Characters/Princess Piggie").unwrap(), "Lore/Characters/PrincessPiggie");
+        assert_eq!(slugify_path("the west/lowercase randy").unwrap(), "TheWest/LowercaseRandy");
+
+        assert!(slugify_path("Lore//Princess Piggie").is_err());
+        assert!(slugify_path("Lore/").is_err());
+    }
 }
 
@@ -0,0 +1,55 @@
+//! Tags on nodes.
+//!
+//! Where a namespace gives a node exactly one place, a node can have any
+//! number of tags. Tags are compared without regard to case, so they are
+//! normalized with [`normalize`] before they are stored.
+
+use serde::{Deserialize, Serialize};
+
+/// A tag, along with how many nodes have it.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Tag {
+    /// The name of the tag.
+    pub name: String,
+    /// How many nodes have the tag.
+    pub nodes: u64,
+}
+
+/// Normalizes the name of a tag.
+///
+/// Surrounding whitespace is trimmed and the name is lowercased. Returns
+/// `None` if the name is empty, longer than 64 characters, or has commas or
+/// control characters in it.
+pub fn normalize(tag: &str) -> Option<String> {
+    // lowercasing can make a name longer, so it comes first
+    let tag = tag.trim().to_lowercase();
+
+    if tag.is_empty() || tag.chars().count() > 64 {
+        return None;
+    }
+
+    if tag.chars().any(|c| c == ',' || c.is_control()) {
+        return None;
+    }
+
+    Some(tag)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_normalize() {
+        use super::normalize;
+
+        assert_eq!(normalize("Lore").as_deref(), Some("lore"));
+        assert_eq!(normalize("  Work In Progress ").as_deref(), Some("work in progress"));
+        assert_eq!(normalize("   "), None);
+        assert_eq!(normalize("a,b"), None);
+        assert_eq!(normalize("a\nb"), None);
+        assert_eq!(normalize(&"a".repeat(65)), None);
+
+        // `İ` is lowercased to two characters
+        assert_eq!(normalize(&"İ".repeat(64)), None);
+        assert_eq!(normalize(&"İ".repeat(32)).map(|tag| tag.chars().count()), Some(64));
+    }
+}
@@ -0,0 +1,35 @@
+//! Administration API.
+
+use ruinaio_model::{image::GcReport, params};
+
+use crate::config::Config;
+use crate::db::Db;
+use crate::error::Error;
+use crate::gc;
+use crate::store::Store;
+
+use actix_web::web;
+
+/// Collects blobs in the object store that no image refers to anymore.
+pub async fn collect_garbage(
+    params: web::Query<params::CollectGarbage>,
+    config: web::Data<Config>,
+    store: Store,
+    db: Db,
+) -> Result<web::Json<GcReport>, Error> {
+    let report = gc::collect(
+        db.get_ref(),
+        store.get_ref(),
+        config.gc_grace_period,
+        params.dry_run,
+    ).await?;
+
+    if !report.dry_run {
+        info!(
+            "collected {} blobs ({} bytes) out of {}",
+            report.collected, report.bytes_reclaimed, report.scanned,
+        );
+    }
+
+    Ok(web::Json(report))
+}
@@ -0,0 +1,437 @@
+//! Node image API.
+//!
+//! Image contents live in the object store under their hash, while the
+//! `images` table maps a node's filenames to those hashes.
+
+use ruinaio_model::image::Image;
+
+use crate::api::node::check_exists;
+use crate::config::Config;
+use crate::db::Db;
+use crate::error::{Code, Error};
+use crate::gc;
+use crate::store::{self, Store};
+
+use actix_web::{HttpRequest, HttpResponse, ResponseError as _, web::{self, BytesMut}};
+use actix_web::http::header::{
+    self, ByteRangeSpec, CacheControl, CacheDirective, ContentDisposition, ContentRange,
+    ContentRangeSpec, ContentType, DispositionParam, DispositionType, ETag, EntityTag, Header,
+    IfNoneMatch, IfRange,
+};
+
+use actix_multipart::{Multipart, MultipartError};
+
+use futures::TryStreamExt as _;
+
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
+/// Keeps browsers from second-guessing the content type of a blob.
+const NOSNIFF: (header::HeaderName, &str) = (header::X_CONTENT_TYPE_OPTIONS, "nosniff");
+
+/// Characters that are escaped in image URLs.
+const URL_ESCAPE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// A type of image that can be uploaded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ImageType {
+    Png,
+    Jpeg,
+    Gif,
+    Webp,
+}
+
+impl ImageType {
+    /// Finds the type of an image by its first bytes.
+    ///
+    /// Filenames and content types are chosen by the client, so they are
+    /// never trusted.
+    fn sniff(data: &[u8]) -> Option<ImageType> {
+        if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(ImageType::Png)
+        } else if data.starts_with(&[0xff, 0xd8, 0xff]) {
+            Some(ImageType::Jpeg)
+        } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+            Some(ImageType::Gif)
+        } else if data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP" {
+            Some(ImageType::Webp)
+        } else {
+            None
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            ImageType::Png => "image/png",
+            ImageType::Jpeg => "image/jpeg",
+            ImageType::Gif => "image/gif",
+            ImageType::Webp => "image/webp",
+        }
+    }
+}
+
+/// Uploads an image to a node.
+///
+/// The image is the first file in a `multipart/form-data` body, and must be a
+/// PNG, JPEG, GIF or WebP image.
+pub async fn upload(
+    id: web::Path<(i32,)>,
+    mut payload: Multipart,
+    config: web::Data<Config>,
+    store: Store,
+    db: Db,
+) -> Result<web::Json<Image>, Error> {
+    let (id,) = id.into_inner();
+
+    // fail before reading the whole upload
+    check_exists(id, db.get_ref()).await?;
+
+    // find the first file
+    let mut field = loop {
+        match payload.try_next().await.map_err(invalid_upload)? {
+            Some(field) => {
+                let has_filename = field
+                    .content_disposition()
+                    .and_then(|cd| cd.get_filename())
+                    .is_some();
+
+                if has_filename {
+                    break field;
+                }
+            }
+            None => return Err(Error::new(Code::InvalidUpload, "upload has no file")),
+        }
+    };
+
+    let filename = field
+        .content_disposition()
+        .and_then(|cd| cd.get_filename())
+        .unwrap_or_default();
+    let filename = check_filename(filename)?.to_owned();
+
+    // read contents
+    let mut data = BytesMut::new();
+
+    while let Some(chunk) = field.try_next().await.map_err(invalid_upload)? {
+        if data.len() + chunk.len() > config.max_image_size {
+            return Err(Error::new(
+                Code::PayloadTooLarge,
+                format!("image cannot be larger than {} bytes", config.max_image_size),
+            ));
+        }
+
+        data.extend_from_slice(&chunk);
+    }
+
+    if ImageType::sniff(&data).is_none() {
+        return Err(Error::new(
+            Code::InvalidUpload,
+            "upload must be a PNG, JPEG, GIF or WebP image",
+        ));
+    }
+
+    let data = data.freeze();
+    let hash = store::hash(&data);
+
+    // reject duplicates before anything is stored
+    let duplicate = sqlx::query_as::<_, (String,)>(
+        "SELECT filename FROM images WHERE node_id = $1 AND (filename = $2 OR hash = $3) LIMIT 1;"
+    )
+        .bind(id)
+        .bind(&filename)
+        .bind(&hash)
+        .fetch_optional(db.get_ref())
+        .await?;
+
+    if let Some((existing,)) = duplicate {
+        return Err(duplicate_image(&filename, &existing));
+    }
+
+    // keep garbage collection from deleting the blob before it is referenced
+    let mut tx = db.begin().await?;
+
+    sqlx::query("SELECT pg_advisory_xact_lock_shared($1);")
+        .bind(gc::LOCK)
+        .execute(&mut tx)
+        .await?;
+
+    store.put(&hash, data).await?;
+
+    let result = sqlx::query(
+        "INSERT INTO images (hash, filename, node_id) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING;"
+    )
+        .bind(&hash)
+        .bind(&filename)
+        .bind(id)
+        .execute(&mut tx)
+        .await?;
+
+    // someone uploaded the same filename in the meantime
+    if result.rows_affected() == 0 {
+        return Err(duplicate_image(&filename, &filename));
+    }
+
+    tx.commit().await?;
+
+    Ok(web::Json(Image {
+        url: image_url(&config, id, &filename),
+        node_id: id,
+        filename,
+        hash,
+    }))
+}
+
+/// Gets an image of a node by its filename.
+///
+/// A filename can be reused once its image is deleted, so the response has to
+/// be revalidated with its `ETag`.
+pub async fn image(
+    path: web::Path<(i32, String)>,
+    req: HttpRequest,
+    store: Store,
+    db: Db,
+) -> Result<HttpResponse, Error> {
+    let (id, filename) = path.into_inner();
+
+    let image = sqlx::query_as::<_, (String,)>(
+        "SELECT hash FROM images WHERE node_id = $1 AND filename = $2;"
+    )
+        .bind(id)
+        .bind(&filename)
+        .fetch_optional(db.get_ref())
+        .await?;
+
+    match image {
+        Some((hash,)) => {
+            let cache = CacheControl(vec![CacheDirective::NoCache]);
+
+            serve(&req, &store, &hash, &filename, cache).await
+        }
+        None => Err(Error::not_found(format!("image `{}` not found", filename))),
+    }
+}
+
+/// Gets an image by its hash.
+///
+/// The contents behind a hash never change, so the response can be cached
+/// forever.
+pub async fn image_by_hash(
+    hash: web::Path<(String,)>,
+    req: HttpRequest,
+    store: Store,
+    db: Db,
+) -> Result<HttpResponse, Error> {
+    let (hash,) = hash.into_inner();
+
+    if !store::is_hash(&hash) {
+        return Err(Error::not_found("image not found"));
+    }
+
+    // only blobs that are still in use are served
+    let image = sqlx::query_as::<_, (String,)>(
+        "SELECT filename FROM images WHERE hash = $1 LIMIT 1;"
+    )
+        .bind(&hash)
+        .fetch_optional(db.get_ref())
+        .await?;
+
+    match image {
+        Some((filename,)) => {
+            let cache = CacheControl(vec![
+                CacheDirective::Public,
+                CacheDirective::MaxAge(31536000),
+                CacheDirective::Extension("immutable".into(), None),
+            ]);
+
+            serve(&req, &store, &hash, &filename, cache).await
+        }
+        None => Err(Error::not_found("image not found")),
+    }
+}
+
+/// Serves a blob, honoring conditional and range requests.
+///
+/// Only blobs that are images by their contents are shown inline. Anything
+/// else, like blobs uploaded before uploads were checked, is sent as a
+/// sandboxed download so it can't run scripts on the API's origin.
+async fn serve(
+    req: &HttpRequest,
+    store: &Store,
+    hash: &str,
+    filename: &str,
+    cache: CacheControl,
+) -> Result<HttpResponse, Error> {
+    let etag = EntityTag::new_strong(hash.to_owned());
+
+    // the client already has it
+    let cached = match parse_header::<IfNoneMatch>(req) {
+        Some(IfNoneMatch::Any) => true,
+        Some(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(&etag)),
+        None => false,
+    };
+
+    if cached {
+        return Ok(HttpResponse::NotModified()
+            .insert_header(NOSNIFF)
+            .insert_header(ETag(etag))
+            .insert_header(cache)
+            .finish());
+    }
+
+    let len = match store.len(hash).await? {
+        Some(len) => len,
+        None => {
+            error!("image {} is missing from object storage", hash);
+            return Err(Error::not_found("image not found"));
+        }
+    };
+
+    // a range only applies if the client has the same contents
+    let range = match parse_header::<IfRange>(req) {
+        Some(IfRange::EntityTag(tag)) if !tag.strong_eq(&etag) => None,
+        Some(IfRange::Date(_)) => None,
+        _ => parse_header::<header::Range>(req),
+    };
+
+    // multiple ranges are served in full, which is allowed
+    let range = match range {
+        Some(header::Range::Bytes(specs)) if specs.len() == 1 => {
+            Some(ByteRangeSpec::to_satisfiable_range(&specs[0], len))
+        }
+        _ => None,
+    };
+    let range = match range {
+        Some(Some((start, end))) => Some(start..end + 1),
+        Some(None) => {
+            let mut res = Error::new(
+                Code::RangeNotSatisfiable,
+                format!("range is outside of {} bytes", len),
+            )
+                .error_response();
+
+            res.headers_mut().insert(NOSNIFF.0, NOSNIFF.1.parse().unwrap());
+            res.headers_mut().insert(
+                header::CONTENT_RANGE,
+                ContentRangeSpec::Bytes { range: None, instance_length: Some(len) }
+                    .to_string()
+                    .parse()
+                    .unwrap(),
+            );
+
+            return Ok(res);
+        }
+        None => None,
+    };
+
+    let mut res = match &range {
+        Some(range) => {
+            let mut res = HttpResponse::PartialContent();
+            res.insert_header(ContentRange(ContentRangeSpec::Bytes {
+                range: Some((range.start, range.end - 1)),
+                instance_length: Some(len),
+            }));
+            res
+        }
+        None => HttpResponse::Ok(),
+    };
+
+    // the first bytes tell what the blob really is
+    let head = store.get(hash, Some(0..len.min(12))).await?;
+
+    match ImageType::sniff(&head) {
+        Some(image_type) => {
+            res.insert_header((header::CONTENT_TYPE, image_type.content_type()));
+        }
+        None => {
+            let mime = mime_guess::from_path(filename).first_or_octet_stream();
+
+            res
+                .insert_header(ContentType(mime))
+                .insert_header(ContentDisposition {
+                    disposition: DispositionType::Attachment,
+                    parameters: vec![DispositionParam::Filename(filename.to_owned())],
+                })
+                .insert_header((header::CONTENT_SECURITY_POLICY, "sandbox"));
+        }
+    }
+
+    let data = store.get(hash, range).await?;
+
+    Ok(res
+        .insert_header(NOSNIFF)
+        .insert_header(ETag(etag))
+        .insert_header(cache)
+        .insert_header((header::ACCEPT_RANGES, "bytes"))
+        .body(data))
+}
+
+fn parse_header<H: Header>(req: &HttpRequest) -> Option<H> {
+    if req.headers().contains_key(H::name()) {
+        H::parse(req).ok()
+    } else {
+        None
+    }
+}
+
+/// Builds the URL an image is served at.
+fn image_url(config: &Config, id: i32, filename: &str) -> String {
+    format!(
+        "{}/node/{}/images/{}",
+        config.base_url.trim_end_matches('/'),
+        id,
+        utf8_percent_encode(filename, URL_ESCAPE),
+    )
+}
+
+fn check_filename(s: &str) -> Result<&str, Error> {
+    // browsers may send a full path
+    let s = s.rsplit(['/', '\\']).next().unwrap_or_default();
+
+    if s.is_empty() || s == "." || s == ".." {
+        return Err(Error::out_of_bounds("filename must be at least 1 character or more"));
+    }
+
+    if s.chars().count() > 256 {
+        return Err(Error::out_of_bounds("filename must be less than or equal to 256 characters"));
+    }
+
+    if s.chars().any(char::is_control) {
+        return Err(Error::out_of_bounds("filename cannot have control characters"));
+    }
+
+    Ok(s)
+}
+
+fn duplicate_image(filename: &str, existing: &str) -> Error {
+    if filename == existing {
+        Error::new(Code::DuplicateImage, format!("image `{}` already exists", filename))
+    } else {
+        Error::new(Code::DuplicateImage, format!("image was already uploaded as `{}`", existing))
+    }
+}
+
+fn invalid_upload(err: MultipartError) -> Error {
+    Error::new(Code::InvalidUpload, format!("malformed upload: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_sniff() {
+        use super::ImageType;
+
+        assert_eq!(ImageType::sniff(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"), Some(ImageType::Png));
+        assert_eq!(ImageType::sniff(b"\xff\xd8\xff\xe0\0\x10JFIF"), Some(ImageType::Jpeg));
+        assert_eq!(ImageType::sniff(b"GIF89a\x01\0\x01\0"), Some(ImageType::Gif));
+        assert_eq!(ImageType::sniff(b"RIFF\x24\0\0\0WEBPVP8 "), Some(ImageType::Webp));
+
+        // anything else is refused, whatever it is named
+        assert_eq!(ImageType::sniff(b"<script>alert(1)</script>"), None);
+        assert_eq!(ImageType::sniff(b"<svg xmlns=\"http://www.w3.org/2000/svg\"/>"), None);
+        assert_eq!(ImageType::sniff(b"RIFF\x24\0\0\0WAVEfmt "), None);
+        assert_eq!(ImageType::sniff(b""), None);
+    }
+}
@@ -0,0 +1,164 @@
+//! Node link API.
+//!
+//! Links are indexed from a node's body every time it is written, see
+//! [`ruinaio_model::link`] for what counts as a link.
+
+use ruinaio_model::{link::{self, DanglingLinks, Link, ResolvedLink}, node::NodeSummary};
+
+use crate::api::node::check_exists;
+use crate::db::Db;
+use crate::error::Error;
+
+use actix_web::web;
+
+use sqlx::{postgres::PgRow, types::Json, Postgres, Row as _, Transaction};
+
+/// Lists the links in a node's body.
+pub async fn links(
+    id: web::Path<(i32,)>,
+    db: Db,
+) -> Result<web::Json<Vec<ResolvedLink>>, Error> {
+    let (id,) = id.into_inner();
+
+    let links = sqlx::query(
+        "SELECT link.target, link.text, link.position, node.id, node.slug, node.title
+        FROM link
+        LEFT JOIN slug_alias ON slug_alias.slug = link.target
+        LEFT JOIN node ON node.slug = link.target OR node.id = slug_alias.node_id
+        WHERE link.source_id = $1 ORDER BY link.position;"
+    )
+        .bind(id)
+        .try_map(|row: PgRow| {
+            let node = match row.try_get::<Option<i32>, _>("id")? {
+                Some(id) => Some(NodeSummary {
+                    id,
+                    slug: row.try_get("slug")?,
+                    title: row.try_get("title")?,
+                }),
+                None => None,
+            };
+
+            Ok(ResolvedLink {
+                link: Link {
+                    target: row.try_get("target")?,
+                    text: row.try_get("text")?,
+                    position: row.try_get::<i32, _>("position")? as usize,
+                },
+                node,
+            })
+        })
+        .fetch_all(db.get_ref())
+        .await?;
+
+    if links.is_empty() {
+        check_exists(id, db.get_ref()).await?;
+    }
+
+    Ok(web::Json(links))
+}
+
+/// Lists the nodes that link to a node.
+pub async fn backlinks(
+    id: web::Path<(i32,)>,
+    db: Db,
+) -> Result<web::Json<Vec<NodeSummary>>, Error> {
+    let (id,) = id.into_inner();
+
+    let nodes = sqlx::query_as::<_, (i32, String, String)>(
+        "SELECT DISTINCT source.id, source.slug, source.title
+        FROM link JOIN node source ON source.id = link.source_id
+        WHERE link.target = (SELECT slug FROM node WHERE id = $1)
+            OR link.target IN (SELECT slug FROM slug_alias WHERE node_id = $1)
+        ORDER BY source.id;"
+    )
+        .bind(id)
+        .fetch_all(db.get_ref())
+        .await?;
+
+    if nodes.is_empty() {
+        check_exists(id, db.get_ref()).await?;
+    }
+
+    Ok(web::Json(
+        nodes
+            .into_iter()
+            .map(|(id, slug, title)| NodeSummary { id, slug, title })
+            .collect()
+    ))
+}
+
+/// Lists every node with links that resolve to no existing node.
+///
+/// Links to the previous slug of a renamed node still resolve to it.
+pub async fn dangling(
+    db: Db,
+) -> Result<web::Json<Vec<DanglingLinks>>, Error> {
+    sqlx::query(
+        "SELECT source.id, source.slug, source.title,
+            json_agg(json_build_object(
+                'target', link.target,
+                'text', link.text,
+                'position', link.position
+            ) ORDER BY link.position) AS links
+        FROM link
+        JOIN node source ON source.id = link.source_id
+        WHERE NOT EXISTS (SELECT 1 FROM node WHERE node.slug = link.target)
+            AND NOT EXISTS (SELECT 1 FROM slug_alias WHERE slug_alias.slug = link.target)
+        GROUP BY source.id ORDER BY source.id;"
+    )
+        .try_map(|row: PgRow| {
+            let Json(links) = row.try_get::<Json<Vec<Link>>, _>("links")?;
+
+            Ok(DanglingLinks {
+                node: NodeSummary {
+                    id: row.try_get("id")?,
+                    slug: row.try_get("slug")?,
+                    title: row.try_get("title")?,
+                },
+                links,
+            })
+        })
+        .fetch_all(db.get_ref())
+        .await
+        .map(web::Json)
+        .map_err(From::from)
+}
+
+/// Replaces the indexed links of a node with the links in its body.
+pub(crate) async fn index(
+    id: i32,
+    body: &str,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<(), Error> {
+    // links too long to ever point at a slug are not worth keeping
+    let links = link::extract(body)
+        .into_iter()
+        .filter(|link| link.target.len() <= 256)
+        .collect::<Vec<_>>();
+
+    sqlx::query("DELETE FROM link WHERE source_id = $1;")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+    if links.is_empty() {
+        return Ok(());
+    }
+
+    let targets = links.iter().map(|link| link.target.as_str()).collect::<Vec<_>>();
+    let texts = links.iter().map(|link| link.text.as_str()).collect::<Vec<_>>();
+    let positions = links.iter().map(|link| link.position as i32).collect::<Vec<_>>();
+
+    sqlx::query(
+        "INSERT INTO link (source_id, target, text, position)
+        SELECT $1, * FROM UNNEST($2::VARCHAR[], $3::TEXT[], $4::INTEGER[]);"
+    )
+        .bind(id)
+        .bind(&targets)
+        .bind(&texts)
+        .bind(&positions)
+        .execute(&mut *tx)
+        .await?;
+
+    Ok(())
+}
@@ -1,15 +1,57 @@
 //! Ruina REST API.
 
+pub mod admin;
+pub mod image;
+pub mod link;
+pub mod namespace;
 pub mod node;
+pub mod relation;
+pub mod revision;
+pub mod search;
+pub mod tag;
+
+use crate::config::Config;
+use crate::error::Error;
 
 use actix_web::web;
 
 /// Configures an actix web application with the API.
-pub fn config(app: &mut web::ServiceConfig) {
+///
+/// Requests that can't be extracted, like malformed JSON bodies or ids that
+/// aren't numbers, are answered with the usual JSON errors.
+pub fn config(app: &mut web::ServiceConfig, config: &Config) {
     app
+        .app_data(web::JsonConfig::default()
+            .limit(config.max_json_size)
+            .error_handler(|err, _| Error::from(err).into())
+        )
+        .app_data(web::QueryConfig::default()
+            .error_handler(|err, _| Error::from(err).into())
+        )
+        .app_data(web::PathConfig::default()
+            .error_handler(|err, _| Error::from(err).into())
+        )
+        .service(web::resource("/admin/gc")
+            .route(web::post().to(admin::collect_garbage))
+        )
+        .service(web::resource("/namespaces")
+            .route(web::get().to(namespace::list))
+        )
+        .service(web::resource("/namespaces/move")
+            .route(web::post().to(namespace::move_namespace))
+        )
         .service(web::resource("/nodes")
             .route(web::get().to(node::list))
         )
+        .service(web::resource("/search")
+            .route(web::get().to(search::search))
+        )
+        .service(web::resource("/autocomplete")
+            .route(web::get().to(search::autocomplete))
+        )
+        .service(web::resource("/links/dangling")
+            .route(web::get().to(link::dangling))
+        )
         .service(web::resource("/nodes/new")
             .route(web::post().to(node::create))
         )
@@ -17,6 +59,61 @@ pub fn config(app: &mut web::ServiceConfig) {
             .route(web::get().to(node::node))
             .route(web::patch().to(node::update))
             .route(web::delete().to(node::delete))
+        )
+        .service(web::resource("/slug/{slug:.*}")
+            .route(web::get().to(node::by_slug))
+        )
+        .service(web::resource("/node/{id}/children")
+            .route(web::get().to(relation::children))
+        )
+        .service(web::resource("/node/{id}/children/{child_id}")
+            .route(web::put().to(relation::attach))
+            .route(web::delete().to(relation::detach))
+        )
+        .service(web::resource("/node/{id}/parents")
+            .route(web::get().to(relation::parents))
+        )
+        .service(web::resource("/node/{id}/ancestors")
+            .route(web::get().to(relation::ancestors))
+        )
+        .service(web::resource("/node/{id}/descendants")
+            .route(web::get().to(relation::descendants))
+        )
+        .service(web::resource("/node/{id}/images")
+            .route(web::post().to(image::upload))
+        )
+        .service(web::resource("/node/{id}/images/{filename}")
+            .route(web::get().to(image::image))
+        )
+        .service(web::resource("/images/{hash}")
+            .route(web::get().to(image::image_by_hash))
+        )
+        .service(web::resource("/node/{id}/links")
+            .route(web::get().to(link::links))
+        )
+        .service(web::resource("/node/{id}/backlinks")
+            .route(web::get().to(link::backlinks))
+        )
+        .service(web::resource("/node/{id}/revisions")
+            .route(web::get().to(revision::list))
+        )
+        .service(web::resource("/node/{id}/revisions/{revision}")
+            .route(web::get().to(revision::revision))
+        )
+        .service(web::resource("/node/{id}/diff")
+            .route(web::get().to(revision::diff))
+        )
+        .service(web::resource("/node/{id}/revisions/{revision}/restore")
+            .route(web::post().to(revision::restore))
+        )
+        .service(web::resource("/tags")
+            .route(web::get().to(tag::list))
+        )
+        .service(web::resource("/node/{id}/tags")
+            .route(web::put().to(tag::set))
+        )
+        .service(web::resource("/node/{id}/tags/{tag}")
+            .route(web::delete().to(tag::remove))
         );
 }
 
@@ -0,0 +1,194 @@
+//! Namespace API.
+
+use ruinaio_model::{params, namespace::{MovedNode, Namespace}};
+
+use crate::api::{node::check_namespace, revision};
+use crate::db::Db;
+use crate::error::{Code, Error};
+
+use std::collections::HashMap;
+
+use actix_web::web;
+
+/// Lists every namespace in a space as a tree.
+pub async fn list(
+    db: Db,
+) -> Result<web::Json<Vec<Namespace>>, Error> {
+    // every node counts towards each of its namespaces, and directly towards
+    // the deepest one
+    let rows = sqlx::query_as::<_, (String, i64, i64)>(
+        "SELECT prefix, COUNT(*) FILTER (WHERE direct), COUNT(*) FROM (
+            SELECT
+                array_to_string(parts[1:depth], '/') || '/' AS prefix,
+                depth = cardinality(parts) - 1 AS direct
+            FROM (SELECT string_to_array(slug, '/') AS parts FROM node) AS node,
+            generate_series(1, cardinality(parts) - 1) AS depth
+        ) AS namespace
+        GROUP BY prefix ORDER BY prefix COLLATE \"C\";"
+    )
+        .fetch_all(db.get_ref())
+        .await?;
+
+    // group every namespace under its parent
+    let mut children = HashMap::<Option<String>, Vec<Namespace>>::new();
+
+    for (prefix, nodes, total_nodes) in rows {
+        let parent = parent(&prefix).map(ToOwned::to_owned);
+
+        children.entry(parent).or_default().push(Namespace {
+            prefix,
+            nodes: nodes as u64,
+            total_nodes: total_nodes as u64,
+            children: Vec::new(),
+        });
+    }
+
+    Ok(web::Json(build_tree(None, &mut children)))
+}
+
+/// Moves a namespace, along with all of its sub-namespaces, into another
+/// namespace.
+///
+/// Either every node is moved or none are. The previous slugs of the nodes are
+/// kept as aliases.
+pub async fn move_namespace(
+    params: web::Json<params::MoveNamespace>,
+    db: Db,
+) -> Result<web::Json<Vec<MovedNode>>, Error> {
+    let params::MoveNamespace { from, to } = params.into_inner();
+
+    let checked_from = check_namespace("from", &from);
+    let checked_to = match to.as_str() {
+        "" => Ok(""),
+        to => check_namespace("to", to),
+    };
+
+    Error::combine([checked_from.as_ref().err(), checked_to.as_ref().err()])?;
+
+    let (from, to) = (checked_from?, checked_to?);
+
+    if to.starts_with(from) {
+        return Err(Error::out_of_bounds("namespace cannot be moved into itself"));
+    }
+
+    let mut tx = db.begin().await?;
+
+    let nodes = sqlx::query_as::<_, (i32, String)>(
+        "SELECT id, slug FROM node WHERE starts_with(slug, $1) ORDER BY id FOR UPDATE;"
+    )
+        .bind(from)
+        .fetch_all(&mut tx)
+        .await?;
+
+    if nodes.is_empty() {
+        return Err(Error::not_found(format!("namespace `{}` not found", from)));
+    }
+
+    let mut ids = Vec::with_capacity(nodes.len());
+    let mut old_slugs = Vec::with_capacity(nodes.len());
+    let mut slugs = Vec::with_capacity(nodes.len());
+
+    for (id, old_slug) in nodes {
+        let slug = format!("{}{}", to, &old_slug[from.len()..]);
+
+        if slug.len() > 256 {
+            return Err(Error::out_of_bounds(format!(
+                "slug of node {} would be longer than 256 characters",
+                id,
+            )));
+        }
+
+        ids.push(id);
+        old_slugs.push(old_slug);
+        slugs.push(slug);
+    }
+
+    // nodes that are not being moved keep their slugs
+    let conflicts = sqlx::query_as::<_, (String,)>(
+        "SELECT slug FROM node WHERE slug = ANY($1) AND NOT starts_with(slug, $2) ORDER BY slug;"
+    )
+        .bind(&slugs)
+        .bind(from)
+        .fetch_all(&mut tx)
+        .await?;
+
+    if !conflicts.is_empty() {
+        let conflicts = conflicts
+            .into_iter()
+            .map(|(slug,)| format!("`{}`", slug))
+            .collect::<Vec<_>>();
+
+        return Err(Error::new(
+            Code::SlugConflict,
+            format!("nodes already exist at {}", conflicts.join(", ")),
+        ));
+    }
+
+    // uniqueness is checked row by row, so when moving into a parent
+    // namespace, a node could briefly take the slug of another moved node.
+    // every slug is cleared out of the way first.
+    sqlx::query("UPDATE node SET slug = '#' || id WHERE id = ANY($1);")
+        .bind(&ids)
+        .execute(&mut tx)
+        .await?;
+
+    sqlx::query(
+        "UPDATE node SET slug = moved.slug, version = node.version + 1
+        FROM UNNEST($1::INTEGER[], $2::VARCHAR[]) AS moved (id, slug)
+        WHERE node.id = moved.id;"
+    )
+        .bind(&ids)
+        .bind(&slugs)
+        .execute(&mut tx)
+        .await?;
+
+    // keep the old slugs around, except for those that were taken again
+    sqlx::query(
+        "INSERT INTO slug_alias (slug, node_id)
+        SELECT * FROM UNNEST($1::VARCHAR[], $2::INTEGER[])
+        ON CONFLICT (slug) DO UPDATE SET node_id = EXCLUDED.node_id;"
+    )
+        .bind(&old_slugs)
+        .bind(&ids)
+        .execute(&mut tx)
+        .await?;
+
+    sqlx::query("DELETE FROM slug_alias WHERE slug = ANY($1);")
+        .bind(&slugs)
+        .execute(&mut tx)
+        .await?;
+
+    revision::record(&ids, false, &mut tx).await?;
+
+    tx.commit().await?;
+
+    Ok(web::Json(
+        ids
+            .into_iter()
+            .zip(old_slugs)
+            .zip(slugs)
+            .map(|((id, old_slug), slug)| MovedNode { id, old_slug, slug })
+            .collect()
+    ))
+}
+
+fn build_tree(
+    prefix: Option<&str>,
+    children: &mut HashMap<Option<String>, Vec<Namespace>>,
+) -> Vec<Namespace> {
+    let mut namespaces = children
+        .remove(&prefix.map(ToOwned::to_owned))
+        .unwrap_or_default();
+
+    for namespace in namespaces.iter_mut() {
+        namespace.children = build_tree(Some(&namespace.prefix), children);
+    }
+
+    namespaces
+}
+
+/// Gets the namespace a namespace is in.
+fn parent(prefix: &str) -> Option<&str> {
+    let (parent, _) = ruinaio_model::slug::split(prefix.trim_end_matches('/'));
+    parent
+}
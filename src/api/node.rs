@@ -1,153 +1,760 @@
 //! Node API.
 
-use ruinaio_model::{params, node::Node, slug, Patch};
+use ruinaio_model::{params::{self, SortKey, SortOrder, TagMode}, node::{Node, NodeSummary, NodeWithRelations, SlugMatch}, error::pointer_token, page::Page, slug, tag, Patch};
 
+use crate::api::{link, revision};
+use crate::config::Config;
 use crate::db::Db;
-use crate::error::{Code, Error};
+use crate::error::{Code, Error, FieldError};
 
 use std::borrow::Cow;
 
-use actix_web::{HttpResponse, web};
+use actix_web::{
+    HttpRequest, HttpResponse, web,
+    http::header::{self, ETag, EntityTag, Header as _, IfMatch},
+};
 
-use sqlx::Row as _;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+
+use serde_json::{Map, Value};
+
+use sqlx::{postgres::PgRow, types::Json, Postgres, QueryBuilder, Row as _, Transaction};
+
+/// The columns of `node` that [`from_row`] expects to be selected, along with
+/// the node's tags.
+pub(crate) const COLUMNS: &str = "node.id, node.slug, node.title, node.body, node.version, node.properties, ARRAY(
+    SELECT tag.name FROM node_tag JOIN tag ON tag.id = node_tag.tag_id
+    WHERE node_tag.node_id = node.id ORDER BY tag.name
+) AS tags";
+
+/// Builds a [`Node`] out of a row selected with [`COLUMNS`].
+pub(crate) fn from_row(row: PgRow) -> Result<Node, sqlx::Error> {
+    Ok(Node {
+        id: row.try_get("id")?,
+        slug: row.try_get("slug")?,
+        title: row.try_get("title")?,
+        body: row.try_get("body")?,
+        version: row.try_get("version")?,
+        tags: Some(row.try_get("tags")?),
+        properties: row.try_get::<Json<_>, _>("properties")?.0,
+    })
+}
 
 /// Lists all the nodes in a space.
+///
+/// Nodes are paged by page number unless a `cursor` is given, in which case
+/// a [`Page`] is returned instead of a plain list.
 pub async fn list(
     params: web::Query<params::ListNodes>,
     db: Db,
-) -> Result<web::Json<Vec<Node>>, Error> {
-    // check bounds
-    if params.page == 0 {
-        return Err(Error::out_of_bounds("member `page` must be greater than zero"));
-    }
+) -> Result<HttpResponse, Error> {
+    let checked_page = check_page(params.page);
+    let checked_limit = check_limit(params.limit, 20);
+    let checked_filters = Filters::new(&params);
+    let checked_sort = check_sort(&params);
+    let checked_cursor = match params.cursor.as_deref() {
+        Some("") | None => Ok(None),
+        Some(cursor) => Cursor::decode(cursor).map(Some),
+    };
+
+    Error::combine([
+        checked_page.as_ref().err(),
+        checked_limit.as_ref().err(),
+        checked_filters.as_ref().err(),
+        checked_sort.as_ref().err(),
+        checked_cursor.as_ref().err(),
+    ])?;
+
+    let filters = checked_filters?;
+
+    let cursor = match &params.cursor {
+        Some(_) => checked_cursor?,
+        None => return list_by_page(&params, &filters, checked_sort?, db).await,
+    };
+
+    // the cursor knows how its pages are sorted
+    let (sort, order) = match &cursor {
+        Some(cursor) => (cursor.sort, cursor.order),
+        None => checked_sort?,
+    };
+
+    let limit = params.limit as i32;
+
+    let mut query = QueryBuilder::new("SELECT ");
+    query.push(COLUMNS).push(" FROM node");
+    filters.push(&mut query);
 
-    if params.limit > 20 {
-        return Err(Error::out_of_bounds("member `limit` cannot be greater than 20"));
+    if let Some(cursor) = &cursor {
+        let op = match order {
+            SortOrder::Asc => ">",
+            SortOrder::Desc => "<",
+        };
+
+        match &cursor.value {
+            Some(value) => {
+                query
+                    .push(format_args!(" AND ({}, node.id) {} (", sort_column(sort), op))
+                    .push_bind(value.clone())
+                    .push(", ")
+                    .push_bind(cursor.id)
+                    .push(")");
+            }
+            None => {
+                query
+                    .push(format_args!(" AND node.id {} ", op))
+                    .push_bind(cursor.id);
+            }
+        }
     }
 
+    push_order(&mut query, sort, order);
+
+    // fetch one more to see if there is a next page
+    query.push(" LIMIT ").push_bind(limit + 1);
+
+    let mut items = query
+        .build()
+        .try_map(from_row)
+        .fetch_all(db.get_ref())
+        .await?;
+
+    let next_cursor = if items.len() > limit as usize {
+        items.truncate(limit as usize);
+
+        items.last().map(|last| {
+            Cursor {
+                sort,
+                order,
+                id: last.id,
+                value: match sort {
+                    SortKey::Id => None,
+                    SortKey::Slug => Some(last.slug.clone()),
+                    SortKey::Title => Some(last.title.clone()),
+                },
+            }
+            .encode()
+        })
+    } else {
+        None
+    };
+
+    let total = if params.total {
+        let mut query = QueryBuilder::new("SELECT COUNT(*) FROM node");
+        filters.push(&mut query);
+
+        let (total,): (i64,) = query
+            .build_query_as()
+            .fetch_one(db.get_ref())
+            .await?;
+
+        Some(total as u64)
+    } else {
+        None
+    };
+
+    Ok(HttpResponse::Ok().json(Page {
+        items,
+        next_cursor,
+        total,
+    }))
+}
+
+async fn list_by_page(
+    params: &params::ListNodes,
+    filters: &Filters,
+    (sort, order): (SortKey, SortOrder),
+    db: Db,
+) -> Result<HttpResponse, Error> {
     let limit = params.limit as i32;
-    let offset = (params.page as i32 - 1) * limit;
+    let offset = (params.page as i64 - 1) * limit as i64;
 
     // return list of nodes
-    sqlx::query(
-        "SELECT id, slug, title, body FROM node LIMIT $1 OFFSET $2;"
-    )
-        .bind(limit)
-        .bind(offset)
-        .try_map(|row| Ok(Node {
-            id: row.try_get(0)?,
-            slug: row.try_get(1)?,
-            title: row.try_get(2)?,
-            body: row.try_get(3)?,
-        }))
+    let mut query = QueryBuilder::new("SELECT ");
+    query.push(COLUMNS).push(" FROM node");
+    filters.push(&mut query);
+    push_order(&mut query, sort, order);
+
+    query
+        .push(" LIMIT ").push_bind(limit)
+        .push(" OFFSET ").push_bind(offset);
+
+    let nodes = query
+        .build()
+        .try_map(from_row)
         .fetch_all(db.get_ref())
-        .await
-        .map(|vec| web::Json(vec))
-        .map_err(From::from)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(nodes))
+}
+
+/// Pushes an `ORDER BY` clause onto a query on `node`.
+fn push_order(query: &mut QueryBuilder<Postgres>, sort: SortKey, order: SortOrder) {
+    let order = match order {
+        SortOrder::Asc => "ASC",
+        SortOrder::Desc => "DESC",
+    };
+
+    // ties are broken by id so pages are stable
+    query.push(format_args!(" ORDER BY {} {}", sort_column(sort), order));
+    if sort != SortKey::Id {
+        query.push(format_args!(", node.id {}", order));
+    }
+}
+
+/// Where a page of a node listing left off.
+///
+/// Cursors are handed out as `sort:order:id:value` in unpadded URL-safe
+/// base64, where `value` is the sort key of the last node, or empty when
+/// sorting by id.
+struct Cursor {
+    sort: SortKey,
+    order: SortOrder,
+    id: i32,
+    value: Option<String>,
+}
+
+impl Cursor {
+    fn encode(&self) -> String {
+        let raw = format!(
+            "{}:{}:{}:{}",
+            self.sort,
+            self.order,
+            self.id,
+            self.value.as_deref().unwrap_or_default(),
+        );
+
+        URL_SAFE_NO_PAD.encode(raw)
+    }
+
+    fn decode(cursor: &str) -> Result<Cursor, Error> {
+        Cursor::parse(cursor)
+            .ok_or_else(|| Error::field("/cursor", Code::OutOfBounds, "member `cursor` is invalid"))
+    }
+
+    fn parse(cursor: &str) -> Option<Cursor> {
+        let raw = URL_SAFE_NO_PAD.decode(cursor).ok()?;
+        let raw = String::from_utf8(raw).ok()?;
+
+        let mut parts = raw.splitn(4, ':');
+        let sort = parts.next()?.parse::<SortKey>().ok()?;
+        let order = parts.next()?.parse::<SortOrder>().ok()?;
+        let id = parts.next()?.parse::<i32>().ok()?;
+        let value = parts.next()?;
+
+        Some(Cursor {
+            sort,
+            order,
+            id,
+            value: match sort {
+                SortKey::Id => None,
+                _ => Some(value.to_owned()),
+            },
+        })
+    }
+}
+
+/// The filters of a node listing, checked.
+struct Filters {
+    namespace: Option<String>,
+    recursive: bool,
+    title: Option<String>,
+    tags: Vec<String>,
+    tag_mode: TagMode,
+    properties: Option<Map<String, Value>>,
+}
+
+impl Filters {
+    /// Checks the filters of a listing, reporting every invalid one.
+    fn new(params: &params::ListNodes) -> Result<Filters, Error> {
+        let namespace = match params.namespace.as_deref() {
+            Some("") | None => Ok(None),
+            Some(namespace) => check_namespace("namespace", namespace).map(|namespace| Some(namespace.to_owned())),
+        };
+
+        let title = match params.title.as_deref() {
+            Some("") | None => Ok(None),
+            Some(title) if title.len() > 128 => Err(Error::field(
+                "/title",
+                Code::OutOfBounds,
+                "member `title` must be less than or equal to 128 characters",
+            )),
+            Some(title) => Ok(Some(title.to_owned())),
+        };
+
+        let tags = Filters::tags(params.tags.as_deref().unwrap_or_default());
+
+        let tag_mode = params.tag_mode.parse::<TagMode>().map_err(|_| {
+            Error::field("/tag_mode", Code::OutOfBounds, "member `tag_mode` must be `all` or `any`")
+        });
+
+        let properties = match params.properties.as_deref() {
+            Some("") | None => Ok(None),
+            Some(properties) => serde_json::from_str(properties).map(Some).map_err(|_| {
+                Error::field("/properties", Code::OutOfBounds, "member `properties` must be a JSON object")
+            }),
+        };
+
+        Error::combine([
+            namespace.as_ref().err(),
+            title.as_ref().err(),
+            tags.as_ref().err(),
+            tag_mode.as_ref().err(),
+            properties.as_ref().err(),
+        ])?;
+
+        Ok(Filters {
+            namespace: namespace?,
+            recursive: params.recursive,
+            title: title?,
+            tags: tags?,
+            tag_mode: tag_mode?,
+            properties: properties?,
+        })
+    }
+
+    /// Checks a comma-separated list of tags, reporting every invalid tag.
+    fn tags(tags: &str) -> Result<Vec<String>, Error> {
+        let checked = tags
+            .split(',')
+            .filter(|tag| !tag.trim().is_empty())
+            .map(|tag| {
+                tag::normalize(tag).ok_or_else(|| {
+                    Error::field("/tags", Code::OutOfBounds, format!("tag `{}` is invalid", tag))
+                })
+            })
+            .collect::<Vec<_>>();
+
+        Error::combine(checked.iter().map(|tag| tag.as_ref().err()))?;
+
+        let mut tags = checked.into_iter().collect::<Result<Vec<_>, _>>()?;
+        tags.sort();
+        tags.dedup();
+
+        if tags.len() > 16 {
+            return Err(Error::field("/tags", Code::OutOfBounds, "member `tags` cannot have more than 16 tags"));
+        }
+
+        Ok(tags)
+    }
+
+    /// Pushes a `WHERE` clause for the filters onto a query on `node`.
+    fn push(&self, query: &mut QueryBuilder<Postgres>) {
+        query.push(" WHERE TRUE");
+
+        if let Some(namespace) = &self.namespace {
+            query
+                .push(" AND starts_with(node.slug, ")
+                .push_bind(namespace.clone())
+                .push(")");
+
+            // the rest of the slug has to be the title
+            if !self.recursive {
+                query
+                    .push(" AND strpos(substr(node.slug, ")
+                    .push_bind(namespace.len() as i32 + 1)
+                    .push("), '/') = 0");
+            }
+        }
+
+        if let Some(title) = &self.title {
+            query
+                .push(" AND node.title ILIKE ")
+                .push_bind(format!("%{}%", escape_like(title)));
+        }
+
+        if !self.tags.is_empty() {
+            let tags = "SELECT 1 FROM node_tag JOIN tag ON tag.id = node_tag.tag_id
+                WHERE node_tag.node_id = node.id AND tag.name = ANY(";
+
+            match self.tag_mode {
+                TagMode::All => {
+                    query
+                        .push(format_args!(" AND (SELECT COUNT(*) FROM ({}", tags))
+                        .push_bind(self.tags.clone())
+                        .push(")) AS tagged) = ")
+                        .push_bind(self.tags.len() as i64);
+                }
+                TagMode::Any => {
+                    query
+                        .push(format_args!(" AND EXISTS ({}", tags))
+                        .push_bind(self.tags.clone())
+                        .push("))");
+                }
+            }
+        }
+
+        if let Some(properties) = &self.properties {
+            // containment narrows it down with the index, but only matches
+            // nested values partially, so each value is compared as well
+            query
+                .push(" AND node.properties @> ")
+                .push_bind(Json(properties.clone()));
+
+            for (key, value) in properties {
+                query
+                    .push(" AND node.properties -> ")
+                    .push_bind(key.clone())
+                    .push(" = ")
+                    .push_bind(Json(value.clone()));
+            }
+        }
+    }
+}
+
+fn check_sort(params: &params::ListNodes) -> Result<(SortKey, SortOrder), Error> {
+    let sort = params.sort.parse::<SortKey>().map_err(|_| {
+        let keys = SortKey::ALL
+            .iter()
+            .map(|key| format!("`{}`", key))
+            .collect::<Vec<_>>();
+
+        Error::field(
+            "/sort",
+            Code::OutOfBounds,
+            format!("member `sort` must be one of {}", keys.join(", ")),
+        )
+    });
+
+    let order = params.order.parse::<SortOrder>().map_err(|_| {
+        Error::field("/order", Code::OutOfBounds, "member `order` must be `asc` or `desc`")
+    });
+
+    Error::combine([sort.as_ref().err(), order.as_ref().err()])?;
+
+    Ok((sort?, order?))
+}
+
+/// Checks the page number of a listing.
+pub(crate) fn check_page(page: u32) -> Result<(), Error> {
+    if page == 0 {
+        Err(Error::field("/page", Code::OutOfBounds, "member `page` must be greater than zero"))
+    } else {
+        Ok(())
+    }
+}
+
+/// Checks the number of items per page of a listing.
+pub(crate) fn check_limit(limit: u32, max: u32) -> Result<(), Error> {
+    if limit > max {
+        Err(Error::field("/limit", Code::OutOfBounds, format!("member `limit` cannot be greater than {}", max)))
+    } else {
+        Ok(())
+    }
+}
+
+fn sort_column(sort: SortKey) -> &'static str {
+    match sort {
+        SortKey::Id => "node.id",
+        SortKey::Slug => "node.slug",
+        SortKey::Title => "node.title",
+    }
+}
+
+/// Checks the keys of properties, reporting every invalid key.
+fn check_properties<'a, I>(keys: I) -> Result<(), Error>
+where
+    I: IntoIterator<Item = &'a String>,
+{
+    // sorted, so the errors come in the same order every time
+    let mut keys = keys.into_iter().collect::<Vec<_>>();
+    keys.sort();
+
+    let errors = keys
+        .into_iter()
+        .filter_map(|key| check_property(key).err())
+        .collect::<Vec<_>>();
+
+    Error::combine(errors.iter().map(Some))
+}
+
+/// Checks the key of a property.
+fn check_property(key: &str) -> Result<(), Error> {
+    let pointer = format!("/properties/{}", pointer_token(key));
+
+    if key.is_empty() {
+        Err(Error::field(pointer, Code::OutOfBounds, "property keys cannot be empty"))
+    } else if key.chars().count() > 64 {
+        Err(Error::field(
+            pointer,
+            Code::OutOfBounds,
+            format!("property key `{}` must be less than or equal to 64 characters", key),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Escapes the wildcards in a string, so it can be used in a `LIKE` pattern.
+pub(crate) fn escape_like(s: &str) -> String {
+    s
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
 }
 
 /// Creates a fresh node.
 pub async fn create(
     params: web::Json<params::CreateNode>,
     db: Db,
-) -> Result<web::Json<Node>, Error> {
-    let params::CreateNode { namespace, title, body } = params.into_inner();
+) -> Result<HttpResponse, Error> {
+    let params::CreateNode { namespace, title, body, properties } = params.into_inner();
 
     let namespace = match namespace {
         Some(namespace) if namespace.is_empty() => None,
         namespace => namespace,
     };
 
+    let checked_namespace = namespace
+        .as_deref()
+        .map(|namespace| check_namespace("namespace", namespace))
+        .transpose();
+    let checked_title = check_title(&title);
+    let checked_properties = check_properties(properties.keys());
+
+    Error::combine([
+        checked_namespace.as_ref().err(),
+        checked_title.as_ref().err(),
+        checked_properties.as_ref().err(),
+    ])?;
+
     // create a slug
-    let slug = check_title(&title)?;
-    let slug = match namespace {
+    let slug = checked_title?;
+    let slug = match checked_namespace? {
         Some(namespace) => {
-            let namespace = check_namespace(&namespace)?.to_owned();
-            (namespace + &slug).into()
+            let slug = namespace.to_owned() + &slug;
+            check_slug_length(&slug, &["namespace", "title"])?;
+            slug.into()
+        }
+        None => {
+            check_slug_length(&slug, &["title"])?;
+            slug
         }
-        None => slug,
     };
 
+    let mut tx = db.begin().await?;
+
     // create new node
     let (id,) = sqlx::query_as::<_, (i32,)>(
-        "INSERT INTO node (slug, title, body) VALUES ($1, $2, $3) RETURNING id;"
+        "INSERT INTO node (slug, title, body, properties) VALUES ($1, $2, $3, $4) RETURNING id;"
     )
         .bind(&slug)
         .bind(&title)
         .bind(&body)
-        .fetch_one(db.get_ref())
+        .bind(Json(&properties))
+        .fetch_one(&mut tx)
         .await?;
 
+    // the slug now belongs to this node
+    sqlx::query("DELETE FROM slug_alias WHERE slug = $1;")
+        .bind(&slug)
+        .execute(&mut tx)
+        .await?;
+
+    link::index(id, &body, &mut tx).await?;
+    revision::record(&[id], false, &mut tx).await?;
+
+    tx.commit().await?;
+
     // return node
-    Ok(web::Json(Node {
-        id,
-        slug: slug.into_owned(),
-        title,
-        body,
-    }))
+    Ok(HttpResponse::Ok()
+        .insert_header(ETag(etag(1)))
+        .json(Node {
+            id,
+            slug: slug.into_owned(),
+            title,
+            body,
+            version: 1,
+            tags: Some(Vec::new()),
+            properties,
+        }))
 }
 
 /// Gets a single node with all of its children and parents.
 pub async fn node(
     id: web::Path<(i32,)>,
     db: Db,
-) -> Result<web::Json<Node>, Error> {
+) -> Result<HttpResponse, Error> {
     let (id,) = id.into_inner();
-    
-    // fetch node
-    let node = sqlx::query_as::<_, (String, String, String)>(
-        "SELECT slug, title, body FROM node WHERE id = $1;"
-    )
+
+    // fetch node, aggregating relations so it only takes one round-trip
+    let sql = format!(
+        "SELECT {},
+            COALESCE((
+                SELECT json_agg(json_build_object('id', p.id, 'slug', p.slug, 'title', p.title) ORDER BY p.id)
+                FROM relation JOIN node p ON p.id = relation.parent_id
+                WHERE relation.child_id = node.id
+            ), '[]') AS parents,
+            COALESCE((
+                SELECT json_agg(json_build_object('id', c.id, 'slug', c.slug, 'title', c.title) ORDER BY c.id)
+                FROM relation JOIN node c ON c.id = relation.child_id
+                WHERE relation.parent_id = node.id
+            ), '[]') AS children
+        FROM node WHERE node.id = $1;",
+        COLUMNS,
+    );
+
+    let node = sqlx::query(&sql)
         .bind(id)
+        .try_map(|row: PgRow| {
+            let Json(parents) = row.try_get::<Json<Vec<NodeSummary>>, _>("parents")?;
+            let Json(children) = row.try_get::<Json<Vec<NodeSummary>>, _>("children")?;
+
+            Ok(NodeWithRelations {
+                node: from_row(row)?,
+                parents,
+                children,
+            })
+        })
         .fetch_optional(db.get_ref())
         .await?;
 
-    if let Some((slug, title, body)) = node {
-        Ok(web::Json(Node { id, slug, title, body }))
-    } else {
-        Err(Error::not_found("node not found"))
+    match node {
+        Some(node) => Ok(HttpResponse::Ok()
+            .insert_header(ETag(etag(node.node.version)))
+            .json(node)),
+        None => Err(Error::not_found("node not found")),
+    }
+}
+
+/// Gets a single node by its full slug, namespace included.
+///
+/// If the slug used to belong to a node that has since been renamed, the node
+/// is returned with a permanent redirect to its current slug.
+pub async fn by_slug(
+    slug: web::Path<(String,)>,
+    config: web::Data<Config>,
+    db: Db,
+) -> Result<HttpResponse, Error> {
+    let (slug,) = slug.into_inner();
+
+    // current slugs win over aliases, though they should never overlap
+    let sql = format!(
+        "SELECT {columns}, NULL AS redirected_from FROM node WHERE slug = $1
+        UNION ALL
+        SELECT {columns}, slug_alias.slug FROM slug_alias
+        JOIN node ON node.id = slug_alias.node_id
+        WHERE slug_alias.slug = $1
+        ORDER BY redirected_from NULLS FIRST LIMIT 1;",
+        columns = COLUMNS,
+    );
+
+    let node = sqlx::query(&sql)
+        .bind(&slug)
+        .try_map(|row: PgRow| Ok(SlugMatch {
+            redirected_from: row.try_get("redirected_from")?,
+            node: from_row(row)?,
+        }))
+        .fetch_optional(db.get_ref())
+        .await?;
+
+    match node {
+        Some(node) if node.redirected_from.is_some() => {
+            let location = format!(
+                "{}/slug/{}",
+                config.base_url.trim_end_matches('/'),
+                node.node.slug,
+            );
+
+            Ok(HttpResponse::MovedPermanently()
+                .insert_header((header::LOCATION, location))
+                .json(node))
+        }
+        Some(node) => Ok(HttpResponse::Ok()
+            .insert_header(ETag(etag(node.node.version)))
+            .json(node)),
+        None => Err(Error::not_found(format!("node `{}` not found", slug))),
     }
 }
 
 /// Updates a single node.
+///
+/// Stale updates are turned away if they have an `If-Match` header or a
+/// `version`, see [`check_version`].
 pub async fn update(
+    req: HttpRequest,
     id: web::Path<(i32,)>,
     params: web::Json<params::UpdateNode>,
     db: Db,
-) -> Result<web::Json<Node>, Error> {
+) -> Result<HttpResponse, Error> {
     let (id,) = id.into_inner();
-    let params::UpdateNode { namespace, title, body } = params.into_inner();
+    let params::UpdateNode { namespace, title, body, properties, version } = params.into_inner();
+    let if_match = if_match(&req)?;
 
     let namespace = match namespace {
         Patch::Some(namespace) if namespace.is_empty() => Patch::Null,
         namespace => namespace,
     };
 
+    let checked_namespace = match &namespace {
+        Patch::Some(namespace) => check_namespace("namespace", namespace).map(Patch::Some),
+        Patch::Null => Ok(Patch::Null),
+        Patch::None => Ok(Patch::None),
+    };
+    let checked_title = title.as_deref().map(check_title).transpose();
+    let checked_properties = check_properties(properties.keys());
+
+    Error::combine([
+        checked_namespace.as_ref().err(),
+        checked_title.as_ref().err(),
+        checked_properties.as_ref().err(),
+    ])?;
+
+    let namespace = checked_namespace?;
+    let title_slug = checked_title?;
+
+    // the members a slug that is too long is blamed on
+    let mut slug_members = Vec::new();
+
+    if namespace.is_some() {
+        slug_members.push("namespace");
+    }
+
+    if title_slug.is_some() {
+        slug_members.push("title");
+    }
+
+    // split property patches into removals and changes
+    let mut removed = Vec::new();
+    let mut changed = Map::new();
+
+    for (key, value) in properties {
+        match value {
+            Patch::Some(value) => {
+                changed.insert(key, value);
+            }
+            Patch::Null => removed.push(key),
+            Patch::None => (),
+        }
+    }
+
+    let mut tx = db.begin().await?;
+
+    if if_match.is_some() || version.is_some() {
+        let current = get_version(id, &mut tx).await?;
+        check_version(current, if_match.as_ref(), version)?;
+    }
+
     // create slug
-    let slug = match (namespace, &title) {
+    let slug = match (namespace, title_slug) {
         // updates both the namespace and title, effectively giving it an
         // entirely new slug
         (Patch::Some(namespace), Some(title)) => {
-            Some(check_namespace(&namespace)?.to_owned() + &check_title(title)?)
+            Some(namespace.to_owned() + &title)
         }
         // unsets the namespace and updates the slug
         (Patch::Null, Some(title)) => {
-            let title = check_title(title)?;
-
             Some(title.into_owned())
         }
         // updates only the namespace
         (Patch::Some(namespace), None) => {
-            let namespace = check_namespace(&namespace)?;
-
-            let slug = get_slug(id, &db).await?;
+            let slug = get_slug(id, &mut tx).await?;
             let (_, title) = slug::split(&slug);
 
             Some(namespace.to_owned() + title)
         }
         // unsets the namespace
         (Patch::Null, None) => {
-            let slug = get_slug(id, &db).await?;
+            let slug = get_slug(id, &mut tx).await?;
 
             let (_, title) = slug::split(&slug);
 
@@ -155,9 +762,7 @@ pub async fn update(
         }
         // updates only the slug
         (Patch::None, Some(title)) => {
-            let title = check_title(title)?;
-
-            let slug = get_slug(id, &db).await?;
+            let slug = get_slug(id, &mut tx).await?;
             let (namespace, _) = slug::split(&slug);
 
             match namespace {
@@ -169,20 +774,50 @@ pub async fn update(
         (Patch::None, None) => None
     };
 
+    if let Some(slug) = &slug {
+        check_slug_length(slug, &slug_members)?;
+    }
+
+    let body_changed = body.is_some();
+
     // update node in database
-    let node = sqlx::query_as::<_, (String, String, String)>(
-        "UPDATE node SET slug = COALESCE($2, slug), title = COALESCE($3, title), body = COALESCE($4, body) WHERE id = $1 RETURNING slug, title, body"
-    )
+    let sql = format!(
+        "WITH old AS (SELECT slug FROM node WHERE id = $1 FOR UPDATE)
+        UPDATE node SET
+            slug = COALESCE($2, node.slug), title = COALESCE($3, title), body = COALESCE($4, body),
+            properties = (properties - $5::TEXT[]) || $6, version = node.version + 1
+        FROM old WHERE id = $1 RETURNING old.slug AS old_slug, {}",
+        COLUMNS,
+    );
+
+    let node = sqlx::query(&sql)
         .bind(id)
         .bind(slug)
         .bind(title)
         .bind(body)
-        .fetch_optional(db.get_ref())
+        .bind(removed)
+        .bind(Json(changed))
+        .try_map(|row: PgRow| Ok((row.try_get::<String, _>("old_slug")?, from_row(row)?)))
+        .fetch_optional(&mut tx)
         .await?;
 
     // retrieve node
-    if let Some((slug, title, body)) = node {
-        Ok(web::Json(Node { id, slug, title, body }))
+    if let Some((old_slug, node)) = node {
+        if old_slug != node.slug {
+            rename(id, &old_slug, &node.slug, &mut tx).await?;
+        }
+
+        if body_changed {
+            link::index(id, &node.body, &mut tx).await?;
+        }
+
+        revision::record(&[id], false, &mut tx).await?;
+
+        tx.commit().await?;
+
+        Ok(HttpResponse::Ok()
+            .insert_header(ETag(etag(node.version)))
+            .json(node))
     } else {
         Err(Error::not_found("node not found"))
     }
@@ -190,30 +825,154 @@ pub async fn update(
 
 /// Delete a single node.
 pub async fn delete(
+    req: HttpRequest,
     id: web::Path<(i32,)>,
     db: Db,
 ) -> Result<HttpResponse, Error> {
     let (id,) = id.into_inner();
+    let if_match = if_match(&req)?;
+
+    let mut tx = db.begin().await?;
+
+    if let Some(if_match) = &if_match {
+        let current = get_version(id, &mut tx).await?;
+        check_version(current, Some(if_match), None)?;
+    }
+
+    // the last revision keeps what the node looked like
+    revision::record(&[id], true, &mut tx).await?;
 
     // delete node in database
     let result = sqlx::query(
         "DELETE FROM node WHERE id = $1"
     )
         .bind(id)
-        .execute(db.get_ref())
+        .execute(&mut tx)
         .await?;
 
     if result.rows_affected() > 0 {
+        tx.commit().await?;
+
         Ok(HttpResponse::NoContent().finish())
     } else {
         Err(Error::not_found("node not found"))
     }
 }
 
-async fn get_slug(id: i32, db: &Db) -> Result<String, Error> {
-    sqlx::query_as::<_, (String,)>("SELECT slug FROM node WHERE id = $1")
+/// Builds the `ETag` of a node at a version.
+pub(crate) fn etag(version: i32) -> EntityTag {
+    EntityTag::new_strong(version.to_string())
+}
+
+/// Gets the `If-Match` header of a request, if it has one.
+fn if_match(req: &HttpRequest) -> Result<Option<IfMatch>, Error> {
+    if req.headers().contains_key(IfMatch::name()) {
+        IfMatch::parse(req)
+            .map(Some)
+            .map_err(|_| Error::out_of_bounds("header `If-Match` is malformed"))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Checks that a write was made against the current version of a node.
+///
+/// The write must match the `If-Match` header and the `version` it was given,
+/// whichever of them are present. A mismatched header fails with a
+/// precondition failed error, while a mismatched version fails with a version
+/// conflict. The header is checked first, so it wins when both mismatch.
+fn check_version(
+    current: i32,
+    if_match: Option<&IfMatch>,
+    version: Option<i32>,
+) -> Result<(), Error> {
+    if let Some(IfMatch::Items(tags)) = if_match {
+        let etag = etag(current);
+
+        if !tags.iter().any(|tag| tag.strong_eq(&etag)) {
+            return Err(Error::new(
+                Code::PreconditionFailed,
+                format!("node has been changed since, its `ETag` is now {}", etag),
+            ));
+        }
+    }
+
+    match version {
+        Some(version) if version != current => Err(Error::new(
+            Code::VersionConflict,
+            format!("node has been changed since version {}, it is now at version {}", version, current),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Gets the version of a node, locking it for the rest of the transaction.
+async fn get_version(
+    id: i32,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<i32, Error> {
+    let version = sqlx::query_as::<_, (i32,)>("SELECT version FROM node WHERE id = $1 FOR UPDATE;")
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+    match version {
+        Some((version,)) => Ok(version),
+        None => Err(Error::not_found("node not found")),
+    }
+}
+
+/// Fails with a not found error if the node does not exist.
+pub(crate) async fn check_exists<'c, E>(id: i32, db: E) -> Result<(), Error>
+where
+    E: sqlx::PgExecutor<'c>,
+{
+    let (exists,) = sqlx::query_as::<_, (bool,)>(
+        "SELECT EXISTS (SELECT 1 FROM node WHERE id = $1);"
+    )
         .bind(id)
-        .fetch_one(db.get_ref())
+        .fetch_one(db)
+        .await?;
+
+    if exists {
+        Ok(())
+    } else {
+        Err(Error::not_found("node not found"))
+    }
+}
+
+/// Keeps the previous slug of a renamed node as an alias of it.
+pub(crate) async fn rename(
+    id: i32,
+    old_slug: &str,
+    new_slug: &str,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<(), Error> {
+    // the node may be taking back one of its old slugs
+    sqlx::query("DELETE FROM slug_alias WHERE slug = $1;")
+        .bind(new_slug)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query(
+        "INSERT INTO slug_alias (slug, node_id) VALUES ($1, $2)
+        ON CONFLICT (slug) DO UPDATE SET node_id = EXCLUDED.node_id;"
+    )
+        .bind(old_slug)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+    Ok(())
+}
+
+async fn get_slug<'c, E>(id: i32, db: E) -> Result<String, Error>
+where
+    E: sqlx::PgExecutor<'c>,
+{
+    sqlx::query_as::<_, (String,)>("SELECT slug FROM node WHERE id = $1 FOR UPDATE")
+        .bind(id)
+        .fetch_one(db)
         .await
         .map_err(|err| match err {
             sqlx::Error::RowNotFound => Error::not_found("node not found"),
@@ -224,31 +983,154 @@ async fn get_slug(id: i32, db: &Db) -> Result<String, Error> {
 
 fn check_title<'a>(s: &'a str) -> Result<Cow<'a, str>, Error> {
     if s.len() == 0 {
-        return Err(Error::out_of_bounds("member `title` must be at least 1 character or more"));
+        return Err(Error::field("/title", Code::OutOfBounds, "member `title` must be at least 1 character or more"));
     }
 
     if s.len() > 128 {
-        return Err(Error::out_of_bounds("member `title` must be less than or equal to 128 characters"));
+        return Err(Error::field("/title", Code::OutOfBounds, "member `title` must be less than or equal to 128 characters"));
     }
 
     // if title is less than 128 characters, the slug should be, too.
-    ruinaio_model::slug::slugify(s).map_err(Into::into)
+    ruinaio_model::slug::slugify(s)
+        .map_err(|err| FieldError::slug("/title", &err).into())
+}
+
+/// Checks that a slug fits in `node.slug`, blaming the members it was made
+/// out of if it doesn't.
+fn check_slug_length(slug: &str, members: &[&str]) -> Result<(), Error> {
+    if slug.chars().count() <= 256 {
+        return Ok(());
+    }
+
+    let errors = members
+        .iter()
+        .map(|member| Error::field(
+            format!("/{}", member),
+            Code::OutOfBounds,
+            format!("member `{}` makes the slug longer than 256 characters", member),
+        ))
+        .collect::<Vec<_>>();
+
+    Error::combine(errors.iter().map(Some))?;
+
+    Err(Error::out_of_bounds("slug must be less than or equal to 256 characters"))
 }
 
-fn check_namespace<'a>(s: &'a str) -> Result<&'a str, Error> {
+/// Checks a namespace given in the member `member`.
+pub(crate) fn check_namespace<'a>(member: &str, s: &'a str) -> Result<&'a str, Error> {
+    let pointer = format!("/{}", member);
+
     if s.len() == 0 {
-        return Err(Error::out_of_bounds("member `namespace` must be at least 1 character or more"));
+        return Err(Error::field(pointer, Code::OutOfBounds, format!("member `{}` must be at least 1 character or more", member)));
     }
 
     if s.len() > 128 {
-        return Err(Error::out_of_bounds("member `namespace` must be less than or equal to 128 characters"));
+        return Err(Error::field(pointer, Code::OutOfBounds, format!("member `{}` must be less than or equal to 128 characters", member)));
     }
 
     if s.chars().last().unwrap() != '/' {
-        return Err(Error::out_of_bounds("member `namespace` must end in a slash"));
+        return Err(Error::field(pointer, Code::OutOfBounds, format!("member `{}` must end in a slash", member)));
     }
 
     ruinaio_model::slug::check_slug(s)
-        .map_err(|err| Error::new(Code::InvalidSlug, err.to_string()))
+        .map_err(|err| FieldError::slug(pointer, &err).into())
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor() {
+        let cursor = Cursor {
+            sort: SortKey::Title,
+            order: SortOrder::Desc,
+            id: 42,
+            value: Some(String::from("Princess Piggie: The West")),
+        };
+
+        // colons in the value survive the round trip
+        let parsed = Cursor::parse(&cursor.encode()).unwrap();
+        assert_eq!((parsed.sort, parsed.order, parsed.id), (SortKey::Title, SortOrder::Desc, 42));
+        assert_eq!(parsed.value.as_deref(), Some("Princess Piggie: The West"));
+
+        // cursors sorted by id have no value
+        let cursor = Cursor {
+            sort: SortKey::Id,
+            order: SortOrder::Asc,
+            id: 7,
+            value: None,
+        };
+
+        let parsed = Cursor::parse(&cursor.encode()).unwrap();
+        assert_eq!((parsed.sort, parsed.order, parsed.id), (SortKey::Id, SortOrder::Asc, 7));
+        assert_eq!(parsed.value, None);
+
+        // an empty value is still a value when sorting by anything else
+        let parsed = Cursor::parse(&URL_SAFE_NO_PAD.encode("slug:asc:1:")).unwrap();
+        assert_eq!(parsed.value.as_deref(), Some(""));
+    }
+
+    #[test]
+    fn test_cursor_malformed() {
+        fn parse(raw: &[u8]) -> bool {
+            Cursor::parse(&URL_SAFE_NO_PAD.encode(raw)).is_some()
+        }
+
+        // not base64, or padded
+        assert!(Cursor::parse("not a cursor!").is_none());
+        assert!(Cursor::parse("aWQ6YXNjOjE6=").is_none());
+
+        // tampered with
+        assert!(!parse(b"views:asc:1:"));
+        assert!(!parse(b"id:up:1:"));
+        assert!(!parse(b"id:asc:one:"));
+        assert!(!parse(b"id:asc:99999999999:"));
+        assert!(!parse(b"id:asc:1"));
+        assert!(!parse(b"title:desc:\xff:x"));
+        assert!(!parse(b""));
+
+        assert!(parse(b"id:asc:1:"));
+
+        // clients are told which member is wrong
+        let error = Cursor::decode("!").err().unwrap();
+        assert_eq!(error.code, Code::OutOfBounds);
+        assert_eq!(error.fields[0].pointer, "/cursor");
+    }
+
+    #[test]
+    fn test_check_version() {
+        use actix_web::{ResponseError as _, http::StatusCode};
+
+        let matching = IfMatch::Items(vec![etag(3)]);
+        let stale = IfMatch::Items(vec![etag(2)]);
+        let weak = IfMatch::Items(vec![EntityTag::new_weak(String::from("3"))]);
+
+        let code = |if_match: Option<&IfMatch>, version| {
+            check_version(3, if_match, version).err().map(|error| error.code)
+        };
+
+        // nothing to check against
+        assert_eq!(code(None, None), None);
+        assert_eq!(code(Some(&IfMatch::Any), None), None);
+
+        // either one alone
+        assert_eq!(code(Some(&matching), None), None);
+        assert_eq!(code(Some(&stale), None), Some(Code::PreconditionFailed));
+        assert_eq!(code(Some(&weak), None), Some(Code::PreconditionFailed));
+        assert_eq!(code(None, Some(3)), None);
+        assert_eq!(code(None, Some(2)), Some(Code::VersionConflict));
+
+        // both have to match, and the header is checked first
+        assert_eq!(code(Some(&matching), Some(3)), None);
+        assert_eq!(code(Some(&stale), Some(3)), Some(Code::PreconditionFailed));
+        assert_eq!(code(Some(&matching), Some(2)), Some(Code::VersionConflict));
+        assert_eq!(code(Some(&stale), Some(2)), Some(Code::PreconditionFailed));
+
+        let error = check_version(3, Some(&stale), Some(2)).unwrap_err();
+        assert_eq!(error.status_code(), StatusCode::PRECONDITION_FAILED);
+        let error = check_version(3, Some(&matching), Some(2)).unwrap_err();
+        assert_eq!(error.status_code(), StatusCode::CONFLICT);
+    }
+}
@@ -0,0 +1,302 @@
+//! Node relation API.
+//!
+//! Relations are directed edges from a parent node to a child node. A node may
+//! have any number of parents and children, but never itself as an ancestor.
+
+use ruinaio_model::{params, node::{Node, NodeTree}};
+
+use crate::api::node::{self as node_api, check_exists};
+use crate::db::Db;
+use crate::error::{Code, Error};
+
+use std::collections::HashMap;
+
+use actix_web::{HttpResponse, web};
+
+use sqlx::{postgres::PgRow, Row as _};
+
+/// Lists the children of a node.
+pub async fn children(
+    id: web::Path<(i32,)>,
+    db: Db,
+) -> Result<web::Json<Vec<Node>>, Error> {
+    let (id,) = id.into_inner();
+
+    let sql = format!(
+        "SELECT {} FROM relation JOIN node ON node.id = relation.child_id WHERE relation.parent_id = $1 ORDER BY node.id;",
+        node_api::COLUMNS,
+    );
+
+    let nodes = sqlx::query(&sql)
+        .bind(id)
+        .try_map(node_api::from_row)
+        .fetch_all(db.get_ref())
+        .await?;
+
+    // an empty list could also mean the node doesn't exist
+    if nodes.is_empty() {
+        check_exists(id, db.get_ref()).await?;
+    }
+
+    Ok(web::Json(nodes))
+}
+
+/// Lists the parents of a node.
+pub async fn parents(
+    id: web::Path<(i32,)>,
+    db: Db,
+) -> Result<web::Json<Vec<Node>>, Error> {
+    let (id,) = id.into_inner();
+
+    let sql = format!(
+        "SELECT {} FROM relation JOIN node ON node.id = relation.parent_id WHERE relation.child_id = $1 ORDER BY node.id;",
+        node_api::COLUMNS,
+    );
+
+    let nodes = sqlx::query(&sql)
+        .bind(id)
+        .try_map(node_api::from_row)
+        .fetch_all(db.get_ref())
+        .await?;
+
+    if nodes.is_empty() {
+        check_exists(id, db.get_ref()).await?;
+    }
+
+    Ok(web::Json(nodes))
+}
+
+/// Attaches a child to a node.
+///
+/// Attaching a child that is already attached does nothing.
+pub async fn attach(
+    ids: web::Path<(i32, i32)>,
+    db: Db,
+) -> Result<HttpResponse, Error> {
+    let (id, child_id) = ids.into_inner();
+
+    if id == child_id {
+        return Err(Error::new(Code::CyclicRelation, "node cannot be its own child"));
+    }
+
+    let mut tx = db.begin().await?;
+
+    // serialize relation writes, otherwise two concurrent attaches could
+    // each pass the cycle check and form a cycle together
+    sqlx::query("LOCK TABLE relation IN SHARE ROW EXCLUSIVE MODE;")
+        .execute(&mut tx)
+        .await?;
+
+    check_exists(id, &mut tx).await?;
+    check_exists(child_id, &mut tx).await?;
+
+    // the parent must not already be a descendant of the child
+    let (cyclic,) = sqlx::query_as::<_, (bool,)>(
+        "WITH RECURSIVE descendant (id) AS (
+            SELECT child_id FROM relation WHERE parent_id = $1
+            UNION
+            SELECT relation.child_id FROM relation JOIN descendant ON relation.parent_id = descendant.id
+        )
+        SELECT EXISTS (SELECT 1 FROM descendant WHERE id = $2);"
+    )
+        .bind(child_id)
+        .bind(id)
+        .fetch_one(&mut tx)
+        .await?;
+
+    if cyclic {
+        return Err(Error::new(Code::CyclicRelation, "node cannot be a child of its own descendant"));
+    }
+
+    sqlx::query(
+        "INSERT INTO relation (parent_id, child_id) VALUES ($1, $2) ON CONFLICT DO NOTHING;"
+    )
+        .bind(id)
+        .bind(child_id)
+        .execute(&mut tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Detaches a child from a node.
+pub async fn detach(
+    ids: web::Path<(i32, i32)>,
+    db: Db,
+) -> Result<HttpResponse, Error> {
+    let (id, child_id) = ids.into_inner();
+
+    let result = sqlx::query(
+        "DELETE FROM relation WHERE parent_id = $1 AND child_id = $2;"
+    )
+        .bind(id)
+        .bind(child_id)
+        .execute(db.get_ref())
+        .await?;
+
+    if result.rows_affected() > 0 {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Err(Error::not_found("relation not found"))
+    }
+}
+
+/// Walks up the relations of a node, up to a maximum depth.
+pub async fn ancestors(
+    id: web::Path<(i32,)>,
+    params: web::Query<params::Traverse>,
+    db: Db,
+) -> Result<HttpResponse, Error> {
+    traverse(id.into_inner().0, &params, Direction::Up, &db).await
+}
+
+/// Walks down the relations of a node, up to a maximum depth.
+pub async fn descendants(
+    id: web::Path<(i32,)>,
+    params: web::Query<params::Traverse>,
+    db: Db,
+) -> Result<HttpResponse, Error> {
+    traverse(id.into_inner().0, &params, Direction::Down, &db).await
+}
+
+#[derive(Clone, Copy)]
+enum Direction {
+    Up,
+    Down,
+}
+
+impl Direction {
+    /// The columns of `relation` that lead from a node to the next.
+    fn columns(self) -> (&'static str, &'static str) {
+        match self {
+            Direction::Up => ("child_id", "parent_id"),
+            Direction::Down => ("parent_id", "child_id"),
+        }
+    }
+}
+
+async fn traverse(
+    id: i32,
+    params: &params::Traverse,
+    direction: Direction,
+    db: &Db,
+) -> Result<HttpResponse, Error> {
+    if params.depth == 0 {
+        return Err(Error::field("/depth", Code::OutOfBounds, "member `depth` must be greater than zero"));
+    }
+
+    if params.depth > 32 {
+        return Err(Error::field("/depth", Code::OutOfBounds, "member `depth` cannot be greater than 32"));
+    }
+
+    let (from, to) = direction.columns();
+    let depth = params.depth as i32;
+
+    let response = if params.nested {
+        // each node is shown once, under the first of its parents one step
+        // closer to the start, so the tree never grows past the nodes in it
+        let sql = format!(
+            "WITH RECURSIVE walk (id, depth) AS (
+                SELECT {to}, 1 FROM relation WHERE {from} = $1
+                UNION
+                SELECT relation.{to}, walk.depth + 1
+                FROM relation JOIN walk ON relation.{from} = walk.id
+                WHERE walk.depth < $2
+            ),
+            reached AS (
+                SELECT id, MIN(depth) AS depth FROM walk WHERE id <> $1 GROUP BY id
+            )
+            SELECT {columns}, reached.depth, (
+                SELECT MIN(relation.{from}) FROM relation
+                LEFT JOIN reached parent ON parent.id = relation.{from}
+                WHERE relation.{to} = reached.id AND CASE
+                    WHEN reached.depth = 1 THEN relation.{from} = $1
+                    ELSE parent.depth = reached.depth - 1
+                END
+            ) AS parent_id
+            FROM reached JOIN node ON node.id = reached.id
+            ORDER BY reached.depth, node.id;",
+            from = from,
+            to = to,
+            columns = node_api::COLUMNS,
+        );
+
+        let rows = sqlx::query(&sql)
+            .bind(id)
+            .bind(depth)
+            .try_map(|row: PgRow| {
+                Ok((
+                    row.try_get::<i32, _>("parent_id")?,
+                    row.try_get::<i32, _>("depth")? as u32,
+                    node_api::from_row(row)?,
+                ))
+            })
+            .fetch_all(db.get_ref())
+            .await?;
+
+        if rows.is_empty() {
+            check_exists(id, db.get_ref()).await?;
+        }
+
+        // group every node under its parent in the tree
+        let mut branches = HashMap::<i32, Vec<(u32, Node)>>::new();
+
+        for (parent_id, depth, node) in rows {
+            branches.entry(parent_id).or_default().push((depth, node));
+        }
+
+        HttpResponse::Ok().json(build_tree(id, &mut branches))
+    } else {
+        // only distinct (node, depth) pairs are kept, so cycles are bounded
+        // by the depth limit
+        let sql = format!(
+            "WITH RECURSIVE walk (id, depth) AS (
+                SELECT {to}, 1 FROM relation WHERE {from} = $1
+                UNION
+                SELECT relation.{to}, walk.depth + 1
+                FROM relation JOIN walk ON relation.{from} = walk.id
+                WHERE walk.depth < $2
+            )
+            SELECT {columns} FROM (
+                SELECT id, MIN(depth) AS depth FROM walk WHERE id <> $1 GROUP BY id
+            ) AS walk JOIN node ON node.id = walk.id
+            ORDER BY walk.depth, node.id;",
+            from = from,
+            to = to,
+            columns = node_api::COLUMNS,
+        );
+
+        let nodes = sqlx::query(&sql)
+            .bind(id)
+            .bind(depth)
+            .try_map(node_api::from_row)
+            .fetch_all(db.get_ref())
+            .await?;
+
+        if nodes.is_empty() {
+            check_exists(id, db.get_ref()).await?;
+        }
+
+        HttpResponse::Ok().json(nodes)
+    };
+
+    Ok(response)
+}
+
+fn build_tree(
+    id: i32,
+    branches: &mut HashMap<i32, Vec<(u32, Node)>>,
+) -> Vec<NodeTree> {
+    let branch = branches.remove(&id).unwrap_or_default();
+
+    branch
+        .into_iter()
+        .map(|(depth, node)| NodeTree {
+            nodes: build_tree(node.id, branches),
+            depth,
+            node,
+        })
+        .collect()
+}
@@ -0,0 +1,334 @@
+//! Node revision API.
+//!
+//! Every write to a node records a revision with [`record`], in the same
+//! transaction as the write.
+
+use ruinaio_model::{diff::{self, Change, NodeDiff}, params, revision::{Revision, RevisionSummary}};
+
+use crate::api::{link, node as node_api};
+use crate::db::Db;
+use crate::error::{Code, Error};
+
+use actix_web::{HttpResponse, http::header::ETag, web};
+
+use serde_json::{Map, Value};
+
+use sqlx::{postgres::PgRow, types::Json, Postgres, Row as _, Transaction};
+
+/// Lists the revisions of a node, newest first.
+///
+/// The revisions of deleted nodes are still listed.
+pub async fn list(
+    id: web::Path<(i32,)>,
+    params: web::Query<params::ListRevisions>,
+    db: Db,
+) -> Result<web::Json<Vec<RevisionSummary>>, Error> {
+    let (id,) = id.into_inner();
+
+    let checked_page = node_api::check_page(params.page);
+    let checked_limit = node_api::check_limit(params.limit, 20);
+
+    Error::combine([checked_page.as_ref().err(), checked_limit.as_ref().err()])?;
+
+    let limit = params.limit as i32;
+    let offset = (params.page as i64 - 1) * limit as i64;
+
+    let revisions = sqlx::query(
+        "SELECT revision, slug, title, deleted, created_at FROM node_revision
+        WHERE node_id = $1 ORDER BY revision DESC LIMIT $2 OFFSET $3;"
+    )
+        .bind(id)
+        .bind(limit)
+        .bind(offset)
+        .try_map(|row: PgRow| {
+            Ok(RevisionSummary {
+                revision: row.try_get("revision")?,
+                slug: row.try_get("slug")?,
+                title: row.try_get("title")?,
+                deleted: row.try_get("deleted")?,
+                created_at: row.try_get("created_at")?,
+            })
+        })
+        .fetch_all(db.get_ref())
+        .await?;
+
+    if revisions.is_empty() {
+        check_history(id, db.get_ref()).await?;
+    }
+
+    Ok(web::Json(revisions))
+}
+
+/// Gets a single revision of a node.
+pub async fn revision(
+    ids: web::Path<(i32, i32)>,
+    db: Db,
+) -> Result<web::Json<Revision>, Error> {
+    let (id, revision) = ids.into_inner();
+
+    fetch(id, revision, db.get_ref()).await.map(web::Json)
+}
+
+/// Restores a node to an old revision, bringing it back if it was deleted.
+///
+/// The old revision is written out as a new one.
+pub async fn restore(
+    ids: web::Path<(i32, i32)>,
+    db: Db,
+) -> Result<HttpResponse, Error> {
+    let (id, revision) = ids.into_inner();
+
+    let mut tx = db.begin().await?;
+
+    let revision = fetch(id, revision, &mut tx).await?;
+
+    let current = sqlx::query_as::<_, (String,)>("SELECT slug FROM node WHERE id = $1 FOR UPDATE;")
+        .bind(id)
+        .fetch_optional(&mut tx)
+        .await?;
+
+    // another node may have taken the slug since
+    let (taken,) = sqlx::query_as::<_, (bool,)>(
+        "SELECT EXISTS (SELECT 1 FROM node WHERE slug = $1 AND id <> $2);"
+    )
+        .bind(&revision.slug)
+        .bind(id)
+        .fetch_one(&mut tx)
+        .await?;
+
+    if taken {
+        return Err(Error::new(
+            Code::SlugConflict,
+            format!("a node already exists at `{}`", revision.slug),
+        ));
+    }
+
+    match current {
+        Some((old_slug,)) => {
+            sqlx::query(
+                "UPDATE node SET slug = $2, title = $3, body = $4, properties = $5, version = version + 1
+                WHERE id = $1;"
+            )
+                .bind(id)
+                .bind(&revision.slug)
+                .bind(&revision.title)
+                .bind(&revision.body)
+                .bind(Json(&revision.properties))
+                .execute(&mut tx)
+                .await?;
+
+            if old_slug != revision.slug {
+                node_api::rename(id, &old_slug, &revision.slug, &mut tx).await?;
+            }
+        }
+        None => {
+            // the node keeps its id, so links to it by id work again, and
+            // counts its version on from before it was deleted
+            sqlx::query(
+                "INSERT INTO node (id, slug, title, body, properties, version)
+                SELECT $1, $2, $3, $4, $5, MAX(version) + 1 FROM node_revision WHERE node_id = $1;"
+            )
+                .bind(id)
+                .bind(&revision.slug)
+                .bind(&revision.title)
+                .bind(&revision.body)
+                .bind(Json(&revision.properties))
+                .execute(&mut tx)
+                .await?;
+
+            sqlx::query("DELETE FROM slug_alias WHERE slug = $1;")
+                .bind(&revision.slug)
+                .execute(&mut tx)
+                .await?;
+        }
+    }
+
+    link::index(id, &revision.body, &mut tx).await?;
+    record(&[id], false, &mut tx).await?;
+
+    let sql = format!("SELECT {} FROM node WHERE id = $1;", node_api::COLUMNS);
+    let node = sqlx::query(&sql)
+        .bind(id)
+        .try_map(node_api::from_row)
+        .fetch_one(&mut tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok()
+        .insert_header(ETag(node_api::etag(node.version)))
+        .json(node))
+}
+
+/// Compares two revisions of a node.
+pub async fn diff(
+    id: web::Path<(i32,)>,
+    params: web::Query<params::Diff>,
+    db: Db,
+) -> Result<HttpResponse, Error> {
+    let (id,) = id.into_inner();
+
+    let checked_format = match params.format.as_str() {
+        "json" => Ok(false),
+        "unified" => Ok(true),
+        _ => Err(Error::field("/format", Code::OutOfBounds, "member `format` must be `json` or `unified`")),
+    };
+    let checked_context = match params.context {
+        0..=100 => Ok(()),
+        _ => Err(Error::field("/context", Code::OutOfBounds, "member `context` cannot be greater than 100")),
+    };
+    let checked_from = match params.from {
+        Some(from) if from < 0 => Err(Error::field("/from", Code::OutOfBounds, "member `from` cannot be negative")),
+        _ => Ok(()),
+    };
+    let checked_to = match params.to {
+        Some(to) if to < 1 => Err(Error::field("/to", Code::OutOfBounds, "member `to` must be greater than zero")),
+        _ => Ok(()),
+    };
+
+    Error::combine([
+        checked_format.as_ref().err(),
+        checked_context.as_ref().err(),
+        checked_from.as_ref().err(),
+        checked_to.as_ref().err(),
+    ])?;
+
+    let unified = checked_format?;
+
+    let to = match params.to {
+        Some(to) => to,
+        None => {
+            let (latest,) = sqlx::query_as::<_, (Option<i32>,)>(
+                "SELECT MAX(revision) FROM node_revision WHERE node_id = $1;"
+            )
+                .bind(id)
+                .fetch_one(db.get_ref())
+                .await?;
+
+            latest.ok_or_else(|| Error::not_found("node not found"))?
+        }
+    };
+
+    let from = params.from.unwrap_or(to - 1);
+
+    // revision 0 is the empty node before the first revision
+    let old = match from {
+        0 => None,
+        from => Some(fetch(id, from, db.get_ref()).await?),
+    };
+    let new = fetch(id, to, db.get_ref()).await?;
+
+    let (old_slug, old_title, old_body) = match old {
+        Some(old) => (old.slug, old.title, old.body),
+        None => Default::default(),
+    };
+
+    // long bodies take a while to compare, so it is kept off the worker
+    let (context, words) = (params.context as usize, params.words);
+    let new_body = new.body;
+    let hunks = web::block(move || diff::diff(&old_body, &new_body, context, words))
+        .await
+        .map_err(Error::internal)?;
+
+    let node_diff = NodeDiff {
+        from,
+        to,
+        slug: Change::new(&old_slug, &new.slug),
+        title: Change::new(&old_title, &new.title),
+        hunks,
+    };
+
+    if unified {
+        let old_name = match from {
+            0 => String::from("/dev/null"),
+            from => format!("{}\t(revision {})", old_slug, from),
+        };
+
+        let text = node_diff.unified(
+            &old_name,
+            &format!("{}\t(revision {})", new.slug, new.revision),
+        );
+
+        Ok(HttpResponse::Ok().content_type("text/x-diff; charset=utf-8").body(text))
+    } else {
+        Ok(HttpResponse::Ok().json(node_diff))
+    }
+}
+
+/// Records the current state of nodes as new revisions.
+///
+/// If `deleted` is set, the revisions mark the nodes as deleted, so this must
+/// be called right before the nodes are deleted. Otherwise, nodes that have
+/// not changed since their last revision are skipped.
+pub(crate) async fn record(
+    ids: &[i32],
+    deleted: bool,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<(), Error> {
+    sqlx::query(
+        "INSERT INTO node_revision (node_id, revision, slug, title, body, properties, version, deleted)
+        SELECT node.id, COALESCE(latest.revision, 0) + 1, node.slug, node.title, node.body, node.properties, node.version, $2
+        FROM node LEFT JOIN LATERAL (
+            SELECT * FROM node_revision WHERE node_id = node.id
+            ORDER BY revision DESC LIMIT 1
+        ) AS latest ON TRUE
+        WHERE node.id = ANY($1) AND (
+            $2 OR latest.revision IS NULL OR latest.deleted
+            OR (latest.slug, latest.title, latest.body, latest.properties)
+                IS DISTINCT FROM (node.slug, node.title, node.body, node.properties)
+        );"
+    )
+        .bind(ids)
+        .bind(deleted)
+        .execute(&mut *tx)
+        .await?;
+
+    Ok(())
+}
+
+async fn fetch<'c, E>(id: i32, revision: i32, db: E) -> Result<Revision, Error>
+where
+    E: sqlx::PgExecutor<'c>,
+{
+    let revision = sqlx::query(
+        "SELECT node_id, revision, slug, title, body, properties, deleted, created_at
+        FROM node_revision WHERE node_id = $1 AND revision = $2;"
+    )
+        .bind(id)
+        .bind(revision)
+        .try_map(|row: PgRow| {
+            Ok(Revision {
+                node_id: row.try_get("node_id")?,
+                revision: row.try_get("revision")?,
+                slug: row.try_get("slug")?,
+                title: row.try_get("title")?,
+                body: row.try_get("body")?,
+                properties: row.try_get::<Json<Map<String, Value>>, _>("properties")?.0,
+                deleted: row.try_get("deleted")?,
+                created_at: row.try_get("created_at")?,
+            })
+        })
+        .fetch_optional(db)
+        .await?;
+
+    revision.ok_or_else(|| Error::not_found("revision not found"))
+}
+
+/// Fails with a not found error if the node never existed.
+async fn check_history<'c, E>(id: i32, db: E) -> Result<(), Error>
+where
+    E: sqlx::PgExecutor<'c>,
+{
+    let (exists,) = sqlx::query_as::<_, (bool,)>(
+        "SELECT EXISTS (SELECT 1 FROM node_revision WHERE node_id = $1);"
+    )
+        .bind(id)
+        .fetch_one(db)
+        .await?;
+
+    if exists {
+        Ok(())
+    } else {
+        Err(Error::not_found("node not found"))
+    }
+}
@@ -0,0 +1,137 @@
+//! Search API.
+
+use ruinaio_model::{node::NodeSummary, params, search::{self, SearchResult, Suggestion}};
+
+use crate::api::node::{check_limit, check_namespace, check_page, escape_like};
+use crate::db::Db;
+use crate::error::{Code, Error};
+
+use actix_web::web;
+
+use sqlx::{postgres::PgRow, Row as _};
+
+/// Searches the titles and bodies of nodes, best matches first.
+///
+/// Bodies are HTML-escaped before snippets are cut out of them, so the only
+/// markup in a snippet is the `<mark>` around matches.
+pub async fn search(
+    params: web::Query<params::Search>,
+    db: Db,
+) -> Result<web::Json<Vec<SearchResult>>, Error> {
+    let checked_page = check_page(params.page);
+    let checked_limit = check_limit(params.limit, 20);
+    let checked_query = check_query(&params.q).and_then(|q| {
+        search::to_tsquery(q).ok_or_else(|| {
+            Error::field("/q", Code::OutOfBounds, "member `q` must have at least one term")
+        })
+    });
+    let checked_namespace = match params.namespace.as_deref() {
+        Some("") | None => Ok(""),
+        Some(namespace) => check_namespace("namespace", namespace),
+    };
+
+    Error::combine([
+        checked_page.as_ref().err(),
+        checked_limit.as_ref().err(),
+        checked_query.as_ref().err(),
+        checked_namespace.as_ref().err(),
+    ])?;
+
+    let query = checked_query?;
+    let namespace = checked_namespace?;
+
+    let limit = params.limit as i32;
+    let offset = (params.page as i64 - 1) * limit as i64;
+
+    let results = sqlx::query(
+        "SELECT
+            node.id, node.slug, node.title,
+            ts_rank(node.search, query) AS rank,
+            ts_headline(
+                'english',
+                replace(replace(replace(replace(replace(
+                    node.body, '&', '&amp;'), '<', '&lt;'), '>', '&gt;'), '\"', '&quot;'), '''', '&#39;'),
+                query,
+                'StartSel=<mark>, StopSel=</mark>, MaxFragments=2'
+            ) AS snippet
+        FROM node, to_tsquery('english', $1) query
+        WHERE node.search @@ query AND starts_with(node.slug, $2)
+        ORDER BY rank DESC, node.id
+        LIMIT $3 OFFSET $4;"
+    )
+        .bind(query)
+        .bind(namespace)
+        .bind(limit)
+        .bind(offset)
+        .try_map(|row: PgRow| {
+            Ok(SearchResult {
+                node: NodeSummary {
+                    id: row.try_get("id")?,
+                    slug: row.try_get("slug")?,
+                    title: row.try_get("title")?,
+                },
+                rank: row.try_get("rank")?,
+                snippet: row.try_get("snippet")?,
+            })
+        })
+        .fetch_all(db.get_ref())
+        .await?;
+
+    Ok(web::Json(results))
+}
+
+/// Suggests nodes for a partially typed title or slug.
+///
+/// Nodes with titles or slugs starting with the query come first, then nodes
+/// that are merely similar to it.
+pub async fn autocomplete(
+    params: web::Query<params::Autocomplete>,
+    db: Db,
+) -> Result<web::Json<Vec<Suggestion>>, Error> {
+    let checked_limit = check_limit(params.limit, 20);
+    let checked_query = check_query(&params.q);
+
+    Error::combine([checked_limit.as_ref().err(), checked_query.as_ref().err()])?;
+
+    let q = checked_query?.trim();
+
+    if q.is_empty() {
+        return Ok(web::Json(Vec::new()));
+    }
+
+    let suggestions = sqlx::query(
+        "SELECT node.id, node.slug, node.title
+        FROM node
+        WHERE node.title ILIKE $2 OR node.slug ILIKE $2
+            OR $1 <% node.title OR $1 <% node.slug
+        ORDER BY
+            (node.title ILIKE $2 OR node.slug ILIKE $2) DESC,
+            greatest(word_similarity($1, node.title), word_similarity($1, node.slug)) DESC,
+            length(node.title), node.id
+        LIMIT $3;"
+    )
+        .bind(q)
+        .bind(format!("{}%", escape_like(q)))
+        .bind(params.limit as i32)
+        .try_map(|row: PgRow| {
+            let node = NodeSummary {
+                id: row.try_get("id")?,
+                slug: row.try_get("slug")?,
+                title: row.try_get("title")?,
+            };
+
+            Ok(Suggestion::from(node))
+        })
+        .fetch_all(db.get_ref())
+        .await?;
+
+    Ok(web::Json(suggestions))
+}
+
+fn check_query(q: &str) -> Result<&str, Error> {
+    if q.len() > 256 {
+        Err(Error::field("/q", Code::OutOfBounds, "member `q` must be less than or equal to 256 characters"))
+    } else {
+        Ok(q)
+    }
+}
@@ -0,0 +1,156 @@
+//! Tag API.
+//!
+//! Tags are created as they are first given to a node, see
+//! [`ruinaio_model::tag`] for what makes a valid tag.
+
+use ruinaio_model::{params, tag::{self, Tag}};
+
+use crate::db::Db;
+use crate::error::{Code, Error};
+
+use actix_web::{HttpResponse, web};
+
+use sqlx::{postgres::PgRow, Row as _};
+
+/// Lists every tag in use, along with how many nodes have it.
+pub async fn list(
+    db: Db,
+) -> Result<web::Json<Vec<Tag>>, Error> {
+    let tags = sqlx::query(
+        "SELECT tag.name, COUNT(*) AS nodes
+        FROM tag JOIN node_tag ON node_tag.tag_id = tag.id
+        GROUP BY tag.name ORDER BY tag.name;"
+    )
+        .try_map(|row: PgRow| {
+            Ok(Tag {
+                name: row.try_get("name")?,
+                nodes: row.try_get::<i64, _>("nodes")? as u64,
+            })
+        })
+        .fetch_all(db.get_ref())
+        .await?;
+
+    Ok(web::Json(tags))
+}
+
+/// Sets the tags of a node, replacing the ones it had.
+///
+/// Returns the node's tags as they were stored.
+pub async fn set(
+    id: web::Path<(i32,)>,
+    params: web::Json<params::SetTags>,
+    db: Db,
+) -> Result<web::Json<Vec<String>>, Error> {
+    let (id,) = id.into_inner();
+    let params = params.into_inner();
+
+    let checked = params.tags
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            tag::normalize(name).ok_or_else(|| Error::field(
+                format!("/tags/{}", i),
+                Code::OutOfBounds,
+                format!("tag `{}` is invalid", name),
+            ))
+        })
+        .collect::<Vec<_>>();
+
+    let mut tags = checked
+        .iter()
+        .filter_map(|tag| tag.as_ref().ok().cloned())
+        .collect::<Vec<_>>();
+
+    tags.sort();
+    tags.dedup();
+
+    let checked_count = if tags.len() > 32 {
+        Err(Error::field("/tags", Code::OutOfBounds, "member `tags` cannot have more than 32 tags"))
+    } else {
+        Ok(())
+    };
+
+    Error::combine(
+        checked
+            .iter()
+            .map(|tag| tag.as_ref().err())
+            .chain([checked_count.as_ref().err()]),
+    )?;
+
+    let mut tx = db.begin().await?;
+
+    // lock the node, so concurrent sets don't mix their tags together
+    let exists = sqlx::query("SELECT 1 FROM node WHERE id = $1 FOR UPDATE;")
+        .bind(id)
+        .fetch_optional(&mut tx)
+        .await?;
+
+    if exists.is_none() {
+        return Err(Error::not_found("node not found"));
+    }
+
+    sqlx::query("INSERT INTO tag (name) SELECT * FROM UNNEST($1::VARCHAR[]) ON CONFLICT DO NOTHING;")
+        .bind(&tags)
+        .execute(&mut tx)
+        .await?;
+
+    sqlx::query(
+        "DELETE FROM node_tag USING tag
+        WHERE node_tag.node_id = $1 AND tag.id = node_tag.tag_id AND NOT tag.name = ANY($2);"
+    )
+        .bind(id)
+        .bind(&tags)
+        .execute(&mut tx)
+        .await?;
+
+    sqlx::query(
+        "INSERT INTO node_tag (node_id, tag_id)
+        SELECT $1, tag.id FROM tag WHERE tag.name = ANY($2)
+        ON CONFLICT DO NOTHING;"
+    )
+        .bind(id)
+        .bind(&tags)
+        .execute(&mut tx)
+        .await?;
+
+    sqlx::query("UPDATE node SET version = version + 1 WHERE id = $1;")
+        .bind(id)
+        .execute(&mut tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(web::Json(tags))
+}
+
+/// Removes a tag from a node.
+pub async fn remove(
+    ids: web::Path<(i32, String)>,
+    db: Db,
+) -> Result<HttpResponse, Error> {
+    let (id, name) = ids.into_inner();
+
+    // a tag that could never be stored can't be on the node either
+    let name = tag::normalize(&name)
+        .ok_or_else(|| Error::not_found("tag not found"))?;
+
+    // the node's version is only counted up if the tag was removed
+    let result = sqlx::query(
+        "WITH removed AS (
+            DELETE FROM node_tag USING tag
+            WHERE node_tag.node_id = $1 AND tag.id = node_tag.tag_id AND tag.name = $2
+            RETURNING node_tag.node_id
+        )
+        UPDATE node SET version = version + 1 WHERE id IN (SELECT node_id FROM removed);"
+    )
+        .bind(id)
+        .bind(name)
+        .execute(db.get_ref())
+        .await?;
+
+    if result.rows_affected() > 0 {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Err(Error::not_found("tag not found"))
+    }
+}
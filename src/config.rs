@@ -0,0 +1,88 @@
+//! Server configuration.
+//!
+//! Everything is read from the environment, which can be set in a `.env` file.
+
+use std::env;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::Context as _;
+
+use log::LevelFilter;
+
+/// The server configuration.
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// The URL of the database to connect to.
+    ///
+    /// Read from `DATABASE_URL`.
+    pub database_url: String,
+    /// The URL the API is served under, used to build links to resources.
+    ///
+    /// Read from `BASE_URL`, defaults to `/api/v1`.
+    pub base_url: String,
+    /// The directory the local object store keeps blobs in.
+    ///
+    /// Read from `STORAGE_PATH`, defaults to `storage`.
+    pub storage_path: PathBuf,
+    /// The largest image that can be uploaded, in bytes.
+    ///
+    /// Read from `MAX_IMAGE_SIZE`, defaults to 10 MiB.
+    pub max_image_size: usize,
+    /// The largest JSON request body that is accepted, in bytes.
+    ///
+    /// Read from `MAX_JSON_SIZE`, defaults to 2 MiB.
+    pub max_json_size: usize,
+    /// How often unused blobs are collected from the object store, or `None`
+    /// if they are only collected through the API.
+    ///
+    /// Read from `GC_INTERVAL` in seconds, disabled by default or if `0`.
+    pub gc_interval: Option<Duration>,
+    /// How long a blob is kept after it was last written before it can be
+    /// collected, so blobs that are still being uploaded are left alone.
+    ///
+    /// Read from `GC_GRACE_PERIOD` in seconds, defaults to one hour.
+    pub gc_grace_period: Duration,
+    /// The most detailed level of logs that are written.
+    ///
+    /// Read from `LOG_LEVEL`, defaults to `info`.
+    pub log_level: LevelFilter,
+    /// Whether the details of internal errors are sent to clients, which
+    /// should only be done during development.
+    ///
+    /// Read from `EXPOSE_ERRORS`, defaults to `false`.
+    pub expose_errors: bool,
+}
+
+impl Config {
+    /// Reads the configuration from the environment.
+    pub fn from_env() -> Result<Config, anyhow::Error> {
+        Ok(Config {
+            database_url: env::var("DATABASE_URL").context("DATABASE_URL must be set")?,
+            base_url: var("BASE_URL", String::from("/api/v1"))?,
+            storage_path: var("STORAGE_PATH", PathBuf::from("storage"))?,
+            max_image_size: var("MAX_IMAGE_SIZE", 10 * 1024 * 1024)?,
+            max_json_size: var("MAX_JSON_SIZE", 2 * 1024 * 1024)?,
+            gc_interval: Some(Duration::from_secs(var("GC_INTERVAL", 0)?))
+                .filter(|interval| !interval.is_zero()),
+            gc_grace_period: Duration::from_secs(var("GC_GRACE_PERIOD", 60 * 60)?),
+            log_level: var("LOG_LEVEL", LevelFilter::Info)?,
+            expose_errors: var("EXPOSE_ERRORS", false)?,
+        })
+    }
+}
+
+fn var<T>(name: &str, default: T) -> Result<T, anyhow::Error>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    match env::var(name) {
+        Ok(value) => value
+            .parse()
+            .with_context(|| format!("{} is invalid", name)),
+        Err(env::VarError::NotPresent) => Ok(default),
+        Err(err) => Err(err).with_context(|| format!("{} is invalid", name)),
+    }
+}
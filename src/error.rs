@@ -1,16 +1,38 @@
 //! Error handling.
+//!
+//! Internal errors, like database or I/O errors, are not shown to clients as
+//! they may have SQL or connection details in them. Instead, they are logged
+//! along with a correlation id, and clients only get the id. See
+//! [`expose_details`] to show them anyway during development.
 
 use std::ops::{Deref, DerefMut};
 use std::fmt::{self, Display, Formatter};
+use std::sync::{Arc, OnceLock};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use actix_web::{HttpResponse, ResponseError, body::BoxBody};
+use actix_web::error::{JsonPayloadError, PathError, PayloadError, QueryPayloadError};
 use actix_web::http::{StatusCode, header::ContentType};
 
-pub use ruinaio_model::error::Code;
+pub use ruinaio_model::error::{Code, FieldError};
+
+static EXPOSE_DETAILS: AtomicBool = AtomicBool::new(false);
+
+/// Sets whether the details of internal errors are sent to clients.
+///
+/// This should only ever be enabled during development.
+pub fn expose_details(expose: bool) {
+    EXPOSE_DETAILS.store(expose, Ordering::Relaxed);
+}
 
 /// A web framework wrapper for a [`ruinaio_model::Error`].
 #[derive(Clone, Debug)]
-pub struct Error(pub ruinaio_model::Error);
+pub struct Error {
+    error: ruinaio_model::Error,
+    /// What caused an internal error, kept away from clients.
+    source: Option<Arc<anyhow::Error>>,
+}
 
 impl Error {
     /// Creates a new `Error`.
@@ -18,10 +40,85 @@ impl Error {
     where
         S: Into<String>,
     {
-        Error(ruinaio_model::Error {
-            code,
-            reason: reason.into(),
-        })
+        Error {
+            error: ruinaio_model::Error {
+                code,
+                reason: reason.into(),
+                id: None,
+                fields: Vec::new(),
+            },
+            source: None,
+        }
+    }
+
+    /// Creates an error for a single invalid field of a request.
+    pub fn field<P, S>(pointer: P, code: Code, message: S) -> Error
+    where
+        P: Into<String>,
+        S: Into<String>,
+    {
+        Error::from(FieldError::new(pointer, code, message))
+    }
+
+    /// Combines errors into one, so every invalid field of a request is
+    /// reported at once.
+    ///
+    /// Handlers check every member of a request before calling this, instead
+    /// of returning on the first invalid one.
+    ///
+    /// The combined error takes the code of the first error. Errors that are
+    /// not about a field are reported as errors with the whole request.
+    pub fn combine<'a, I>(errors: I) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = Option<&'a Error>>,
+    {
+        let mut errors = errors.into_iter().flatten();
+
+        let mut combined = match errors.next() {
+            Some(error) => error.clone(),
+            None => return Ok(()),
+        };
+
+        for error in errors {
+            if combined.fields.is_empty() {
+                let field = FieldError::new("", combined.code, combined.reason.clone());
+                combined.fields.push(field);
+            }
+
+            if error.fields.is_empty() {
+                combined.fields.push(FieldError::new("", error.code, error.reason.clone()));
+            } else {
+                combined.fields.extend(error.fields.iter().cloned());
+            }
+        }
+
+        if combined.fields.len() > 1 {
+            combined.reason = combined.fields
+                .iter()
+                .map(|field| field.message.as_str())
+                .collect::<Vec<_>>()
+                .join("; ");
+        }
+
+        Err(combined)
+    }
+
+    /// Creates an internal server error caused by another error.
+    ///
+    /// The error is given a correlation id, so it can be found in the logs.
+    pub fn internal<E>(source: E) -> Error
+    where
+        E: Into<anyhow::Error>,
+    {
+        Error {
+            error: ruinaio_model::Error {
+                code: Code::InternalServerError,
+                reason: String::from("internal server error"),
+                id: Some(correlation_id()),
+                fields: Vec::new(),
+            },
+            source: Some(Arc::new(source.into())),
+        }
     }
 
     /// Creates a not found error with a specified message.
@@ -43,7 +140,12 @@ impl Error {
 
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        Display::fmt(&self.0, f)
+        Display::fmt(&self.error, f)?;
+
+        match &self.source {
+            Some(source) => write!(f, ": {:#}", source),
+            None => Ok(()),
+        }
     }
 }
 
@@ -52,29 +154,146 @@ impl ResponseError for Error {
         match self.code {
             Code::NotFound => StatusCode::NOT_FOUND,
             Code::InternalServerError => StatusCode::INTERNAL_SERVER_ERROR,
-            Code::InvalidSlug | Code::OutOfBounds => StatusCode::BAD_REQUEST,
+            Code::InvalidSlug | Code::OutOfBounds | Code::InvalidUpload
+                | Code::InvalidJson | Code::InvalidQuery | Code::InvalidPath => StatusCode::BAD_REQUEST,
+            Code::CyclicRelation | Code::DuplicateImage | Code::SlugConflict | Code::VersionConflict => StatusCode::CONFLICT,
+            Code::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+            Code::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            Code::RangeNotSatisfiable => StatusCode::RANGE_NOT_SATISFIABLE,
+            Code::UnsupportedMediaType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
         }
     }
 
     fn error_response(&self) -> HttpResponse<BoxBody> {
+        let mut error = self.error.clone();
+
+        if let Some(source) = &self.source {
+            let id = error.id.as_deref().unwrap_or_default();
+            error!("internal error {}: {:?}", id, source);
+
+            if EXPOSE_DETAILS.load(Ordering::Relaxed) {
+                error.reason = format!("{:#}", source);
+            }
+        }
+
         HttpResponse::build(self.status_code())
             .content_type(ContentType::json())
-            .body(serde_json::to_string(&self.0).unwrap())
+            .body(serde_json::to_string(&error).unwrap())
+    }
+}
+
+
+impl From<sqlx::Error> for Error {
+    fn from(error: sqlx::Error) -> Error {
+        if let sqlx::Error::Database(db) = &error {
+            let constraint = db.constraint().unwrap_or_default();
+
+            match db.code().as_deref() {
+                // unique_violation
+                Some("23505") => match constraint {
+                    "node_slug_key" | "slug_alias_pkey" => {
+                        return Error::new(Code::SlugConflict, "a node with the same slug already exists");
+                    }
+                    "images_pkey" => {
+                        return Error::new(Code::DuplicateImage, "an image with the same name already exists");
+                    }
+                    _ => (),
+                },
+                // foreign_key_violation; everything but tags refers to nodes
+                Some("23503") => {
+                    return match constraint {
+                        "node_tag_tag_id_fkey" => Error::not_found("tag not found"),
+                        _ => Error::not_found("node not found"),
+                    };
+                }
+                // string_data_right_truncation, should be caught beforehand
+                Some("22001") => {
+                    return Error::out_of_bounds("value is too long");
+                }
+                _ => (),
+            }
+        }
+
+        Error::internal(error)
     }
 }
 
-impl<T> From<T> for Error
-where
-    T: std::error::Error
-{
-    fn from(error: T) -> Error {
-        Error::new(Code::InternalServerError, error.to_string())
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Error {
+        Error::internal(error)
+    }
+}
+
+impl From<ruinaio_model::slug::Error> for Error {
+    fn from(error: ruinaio_model::slug::Error) -> Error {
+        Error::new(Code::InvalidSlug, error.to_string())
+    }
+}
+
+impl From<JsonPayloadError> for Error {
+    fn from(error: JsonPayloadError) -> Error {
+        match error {
+            JsonPayloadError::OverflowKnownLength { limit, .. }
+            | JsonPayloadError::Overflow { limit } => Error::new(
+                Code::PayloadTooLarge,
+                format!("request body cannot be larger than {} bytes", limit),
+            ),
+            JsonPayloadError::Payload(PayloadError::Overflow) => Error::new(
+                Code::PayloadTooLarge,
+                "request body is larger than allowed",
+            ),
+            JsonPayloadError::ContentType => Error::new(
+                Code::UnsupportedMediaType,
+                "request body must be `application/json`",
+            ),
+            JsonPayloadError::Deserialize(err) => Error::new(
+                Code::InvalidJson,
+                format!("request body is invalid: {}", err),
+            ),
+            JsonPayloadError::Payload(err) => Error::new(
+                Code::InvalidJson,
+                format!("request body could not be read: {}", err),
+            ),
+            err => Error::internal(err),
+        }
+    }
+}
+
+impl From<QueryPayloadError> for Error {
+    fn from(error: QueryPayloadError) -> Error {
+        match error {
+            QueryPayloadError::Deserialize(err) => Error::new(
+                Code::InvalidQuery,
+                format!("query string is invalid: {}", err),
+            ),
+            err => Error::internal(err),
+        }
+    }
+}
+
+impl From<PathError> for Error {
+    fn from(error: PathError) -> Error {
+        match error {
+            PathError::Deserialize(err) => Error::new(
+                Code::InvalidPath,
+                format!("path is invalid: {}", err),
+            ),
+            err => Error::internal(err),
+        }
+    }
+}
+
+impl From<FieldError> for Error {
+    fn from(field: FieldError) -> Error {
+        let mut error = Error::new(field.code, field.message.clone());
+        error.fields.push(field);
+        error
     }
 }
 
 impl From<Error> for ruinaio_model::Error {
     fn from(e: Error) -> ruinaio_model::Error {
-        e.0
+        e.error
     }
 }
 
@@ -82,13 +301,151 @@ impl Deref for Error {
     type Target = ruinaio_model::Error;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.error
     }
 }
 
 impl DerefMut for Error {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+        &mut self.error
     }
 }
 
+/// Makes an id that is unique for the lifetime of the process, and unlikely
+/// to repeat across restarts or other instances of the server.
+fn correlation_id() -> String {
+    static PROCESS: OnceLock<u32> = OnceLock::new();
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    // drawn once, so two processes started in the same second still differ
+    let process = *PROCESS.get_or_init(|| {
+        let mut bytes = [0; 4];
+
+        match getrandom::getrandom(&mut bytes) {
+            Ok(()) => u32::from_ne_bytes(bytes),
+            Err(_) => std::process::id(),
+        }
+    });
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default();
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+
+    format!("{:08x}-{:x}-{:x}", process, now, count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_combine() {
+        let title = Error::field("/title", Code::OutOfBounds, "title is too long");
+        let namespace = Error::field("/namespace", Code::InvalidSlug, "namespace is invalid");
+        let whole = Error::out_of_bounds("request is invalid");
+
+        // nothing to combine
+        assert!(Error::combine([None, None]).is_ok());
+
+        // a single error is kept as-is
+        let error = Error::combine([None, Some(&title)]).unwrap_err();
+        assert_eq!(error.code, Code::OutOfBounds);
+        assert_eq!(error.reason, "title is too long");
+        assert_eq!(error.fields.len(), 1);
+
+        let error = Error::combine([Some(&whole)]).unwrap_err();
+        assert_eq!(error.reason, "request is invalid");
+        assert!(error.fields.is_empty());
+
+        // the first error's code wins, and every field is kept in order
+        let error = Error::combine([Some(&namespace), Some(&title)]).unwrap_err();
+        assert_eq!(error.code, Code::InvalidSlug);
+        assert_eq!(error.reason, "namespace is invalid; title is too long");
+        assert_eq!(
+            error.fields.iter().map(|field| field.pointer.as_str()).collect::<Vec<_>>(),
+            ["/namespace", "/title"],
+        );
+
+        // errors that aren't about a field are about the whole request
+        let error = Error::combine([Some(&whole), Some(&title)]).unwrap_err();
+        assert_eq!(error.code, Code::OutOfBounds);
+        assert_eq!(error.reason, "request is invalid; title is too long");
+        assert_eq!(
+            error.fields.iter().map(|field| field.pointer.as_str()).collect::<Vec<_>>(),
+            ["", "/title"],
+        );
+
+        let error = Error::combine([Some(&title), Some(&whole)]).unwrap_err();
+        assert_eq!(error.fields[1].pointer, "");
+        assert_eq!(error.fields[1].message, "request is invalid");
+    }
+
+    #[test]
+    fn test_payload_errors() {
+        use std::collections::HashMap;
+
+        use actix_web::web;
+
+        fn check(error: Error, status: StatusCode, code: Code) {
+            assert_eq!(error.status_code(), status);
+            assert_eq!(error.code, code);
+        }
+
+        let json_error = || serde_json::from_str::<u32>("x").unwrap_err();
+        let query_error = || match web::Query::<HashMap<String, u32>>::from_query("page=x") {
+            Err(QueryPayloadError::Deserialize(err)) => err,
+            _ => unreachable!(),
+        };
+
+        // bodies
+        check(
+            JsonPayloadError::OverflowKnownLength { length: 2048, limit: 1024 }.into(),
+            StatusCode::PAYLOAD_TOO_LARGE,
+            Code::PayloadTooLarge,
+        );
+        check(
+            JsonPayloadError::Overflow { limit: 1024 }.into(),
+            StatusCode::PAYLOAD_TOO_LARGE,
+            Code::PayloadTooLarge,
+        );
+        check(
+            JsonPayloadError::Payload(PayloadError::Overflow).into(),
+            StatusCode::PAYLOAD_TOO_LARGE,
+            Code::PayloadTooLarge,
+        );
+        check(
+            JsonPayloadError::ContentType.into(),
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            Code::UnsupportedMediaType,
+        );
+        check(
+            JsonPayloadError::Deserialize(json_error()).into(),
+            StatusCode::BAD_REQUEST,
+            Code::InvalidJson,
+        );
+        check(
+            JsonPayloadError::Payload(PayloadError::Incomplete(None)).into(),
+            StatusCode::BAD_REQUEST,
+            Code::InvalidJson,
+        );
+        check(
+            JsonPayloadError::Serialize(json_error()).into(),
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Code::InternalServerError,
+        );
+
+        // query strings and paths
+        check(
+            QueryPayloadError::Deserialize(query_error()).into(),
+            StatusCode::BAD_REQUEST,
+            Code::InvalidQuery,
+        );
+        check(
+            PathError::Deserialize(query_error()).into(),
+            StatusCode::BAD_REQUEST,
+            Code::InvalidPath,
+        );
+    }
+}
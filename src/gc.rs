@@ -0,0 +1,75 @@
+//! Object store garbage collection.
+//!
+//! Deleting a node cascades to its `images` rows, but not to the blobs they
+//! refer to. Blobs no row refers to anymore are collected here.
+
+use ruinaio_model::image::GcReport;
+
+use crate::error::Error;
+use crate::store::ObjectStore;
+
+use std::collections::HashSet;
+use std::time::{Duration, SystemTime};
+
+use sqlx::PgPool;
+
+/// The advisory lock collection holds exclusively while it deletes blobs.
+///
+/// Uploads hold it shared while they write a blob and refer to it, so a blob
+/// that was just written again is never deleted from under its new image.
+pub(crate) const LOCK: i64 = 0x7275_696e_615f_6763;
+
+/// Deletes every blob no image refers to.
+///
+/// Blobs written in the last `grace_period` are kept, since their image may
+/// not have been recorded yet. If `dry_run` is set, nothing is deleted.
+pub async fn collect(
+    db: &PgPool,
+    store: &dyn ObjectStore,
+    grace_period: Duration,
+    dry_run: bool,
+) -> Result<GcReport, Error> {
+    let cutoff = SystemTime::now() - grace_period;
+
+    // blobs are listed before references are, so a blob is never missed
+    // because it was referenced in between
+    let blobs = store.list().await?;
+
+    let mut tx = db.begin().await?;
+
+    sqlx::query("SELECT pg_advisory_xact_lock($1);")
+        .bind(LOCK)
+        .execute(&mut tx)
+        .await?;
+
+    let referenced = sqlx::query_as::<_, (String,)>("SELECT DISTINCT hash FROM images;")
+        .fetch_all(&mut tx)
+        .await?
+        .into_iter()
+        .map(|(hash,)| hash)
+        .collect::<HashSet<_>>();
+
+    let mut report = GcReport {
+        dry_run,
+        scanned: blobs.len() as u64,
+        collected: 0,
+        bytes_reclaimed: 0,
+    };
+
+    for blob in blobs {
+        if referenced.contains(&blob.hash) || blob.modified > cutoff {
+            continue;
+        }
+
+        if !dry_run {
+            store.delete(&blob.hash).await?;
+        }
+
+        report.collected += 1;
+        report.bytes_reclaimed += blob.len;
+    }
+
+    tx.commit().await?;
+
+    Ok(report)
+}
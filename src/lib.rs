@@ -4,6 +4,10 @@
 extern crate log;
 
 pub mod api;
+pub mod config;
 pub mod db;
 pub mod error;
+pub mod gc;
+pub mod logger;
+pub mod store;
 
@@ -0,0 +1,25 @@
+//! Logging to standard error.
+
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+struct Logger;
+
+impl Log for Logger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        eprintln!("[{} {}] {}", record.level(), record.target(), record.args());
+    }
+
+    fn flush(&self) {}
+}
+
+/// Sets up logging, writing everything up to `level` to standard error.
+pub fn init(level: LevelFilter) -> Result<(), SetLoggerError> {
+    log::set_logger(&Logger)?;
+    log::set_max_level(level);
+
+    Ok(())
+}
@@ -1,23 +1,61 @@
 #[macro_use]
 extern crate log;
 
-use actix_web::{App, HttpServer, web};
+use actix_web::{App, HttpServer, rt, web};
 
-use std::env;
+use ruinaio::config::Config;
+use ruinaio::store::{FsStore, ObjectStore};
+
+use std::sync::Arc;
 
 #[actix_web::main]
 async fn main() -> Result<(), anyhow::Error> {
     dotenv::dotenv().ok();
 
+    let config = Config::from_env()?;
+
+    ruinaio::logger::init(config.log_level)?;
+    ruinaio::error::expose_details(config.expose_errors);
+
+    if config.expose_errors {
+        warn!("internal error details are exposed to clients");
+    }
+
     info!("establishing connection to database");
 
-    let database_url = env::var("DATABASE_URL")?;
-    let database = sqlx::PgPool::connect(&database_url).await?;
+    let database = sqlx::PgPool::connect(&config.database_url).await?;
+
+    let store: Arc<dyn ObjectStore> = Arc::new(FsStore::new(&config.storage_path)?);
+
+    // collect unused blobs on a schedule
+    if let Some(period) = config.gc_interval {
+        let database = database.clone();
+        let store = store.clone();
+        let grace_period = config.gc_grace_period;
+
+        rt::spawn(async move {
+            let mut interval = rt::time::interval(period);
+
+            loop {
+                interval.tick().await;
+
+                match ruinaio::gc::collect(&database, store.as_ref(), grace_period, false).await {
+                    Ok(report) => info!(
+                        "collected {} blobs ({} bytes) out of {}",
+                        report.collected, report.bytes_reclaimed, report.scanned,
+                    ),
+                    Err(err) => error!("failed to collect blobs: {}", err),
+                }
+            }
+        });
+    }
 
     HttpServer::new(move || {
         App::new()
             .app_data(web::Data::new(database.clone()))
-            .configure(ruinaio::api::config)
+            .app_data(web::Data::new(config.clone()))
+            .app_data(web::Data::from(store.clone()))
+            .configure(|app| ruinaio::api::config(app, &config))
     })
     .bind(("127.0.0.1", 9000))?
     .run()
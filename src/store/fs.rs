@@ -0,0 +1,165 @@
+//! Local filesystem object store.
+
+use super::{Blob, ObjectStore};
+
+use std::fs::{self, File};
+use std::io::{self, Read as _, Seek as _, SeekFrom};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+
+use actix_web::web::{self, Bytes};
+
+use futures::future::BoxFuture;
+
+/// An [`ObjectStore`] keeping blobs in a local directory.
+///
+/// Blobs are spread across subdirectories named after the first two
+/// characters of their hash.
+#[derive(Debug)]
+pub struct FsStore {
+    root: PathBuf,
+    temp_count: AtomicU64,
+}
+
+impl FsStore {
+    /// Creates a new `FsStore`, creating the directory if it is missing.
+    pub fn new<P>(root: P) -> io::Result<FsStore>
+    where
+        P: Into<PathBuf>,
+    {
+        let root = root.into();
+        fs::create_dir_all(&root)?;
+
+        Ok(FsStore {
+            root,
+            temp_count: AtomicU64::new(0),
+        })
+    }
+
+    fn path(&self, hash: &str) -> PathBuf {
+        debug_assert!(super::is_hash(hash));
+
+        self.root.join(&hash[..2]).join(hash)
+    }
+}
+
+impl ObjectStore for FsStore {
+    fn put<'a>(&'a self, hash: &'a str, data: Bytes) -> BoxFuture<'a, io::Result<()>> {
+        let path = self.path(hash);
+        let temp = self.root.join(format!(
+            ".{}.{}",
+            hash,
+            self.temp_count.fetch_add(1, Ordering::Relaxed),
+        ));
+
+        Box::pin(blocking(move || {
+            if path.exists() {
+                // keep the blob from being collected before it is referenced
+                return File::options()
+                    .write(true)
+                    .open(&path)?
+                    .set_modified(SystemTime::now());
+            }
+
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            // write somewhere else first so a half-written blob is never seen
+            // under its hash
+            fs::write(&temp, &data)?;
+            fs::rename(&temp, &path).inspect_err(|_| {
+                let _ = fs::remove_file(&temp);
+            })
+        }))
+    }
+
+    fn len<'a>(&'a self, hash: &'a str) -> BoxFuture<'a, io::Result<Option<u64>>> {
+        let path = self.path(hash);
+
+        Box::pin(blocking(move || match fs::metadata(path) {
+            Ok(metadata) => Ok(Some(metadata.len())),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }))
+    }
+
+    fn get<'a>(
+        &'a self,
+        hash: &'a str,
+        range: Option<Range<u64>>,
+    ) -> BoxFuture<'a, io::Result<Bytes>> {
+        let path = self.path(hash);
+
+        Box::pin(blocking(move || match range {
+            Some(range) => {
+                let mut file = File::open(path)?;
+                let mut data = Vec::with_capacity((range.end - range.start) as usize);
+
+                file.seek(SeekFrom::Start(range.start))?;
+                file.take(range.end - range.start).read_to_end(&mut data)?;
+
+                Ok(Bytes::from(data))
+            }
+            None => fs::read(path).map(Bytes::from),
+        }))
+    }
+
+    fn list(&self) -> BoxFuture<'_, io::Result<Vec<Blob>>> {
+        let root = self.root.clone();
+
+        Box::pin(blocking(move || {
+            let mut blobs = Vec::new();
+
+            for dir in fs::read_dir(root)? {
+                let dir = dir?;
+
+                if !dir.file_type()?.is_dir() {
+                    continue;
+                }
+
+                for entry in fs::read_dir(dir.path())? {
+                    let entry = entry?;
+
+                    // skips anything the store did not write
+                    let hash = match entry.file_name().into_string() {
+                        Ok(hash) if super::is_hash(&hash) => hash,
+                        _ => continue,
+                    };
+
+                    let metadata = entry.metadata()?;
+
+                    blobs.push(Blob {
+                        hash,
+                        len: metadata.len(),
+                        modified: metadata.modified()?,
+                    });
+                }
+            }
+
+            Ok(blobs)
+        }))
+    }
+
+    fn delete<'a>(&'a self, hash: &'a str) -> BoxFuture<'a, io::Result<()>> {
+        let path = self.path(hash);
+
+        Box::pin(blocking(move || match fs::remove_file(path) {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err),
+        }))
+    }
+}
+
+async fn blocking<F, R>(f: F) -> io::Result<R>
+where
+    F: FnOnce() -> io::Result<R> + Send + 'static,
+    R: Send + 'static,
+{
+    web::block(f)
+        .await
+        .map_err(io::Error::other)?
+}
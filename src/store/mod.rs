@@ -0,0 +1,69 @@
+//! Object storage.
+//!
+//! Blobs are addressed by the SHA-256 hash of their contents, so a blob never
+//! changes once it is written.
+
+mod fs;
+
+pub use fs::FsStore;
+
+use std::io;
+use std::ops::Range;
+use std::time::SystemTime;
+
+use actix_web::web::{self, Bytes};
+
+use futures::future::BoxFuture;
+
+use sha2::{Digest, Sha256};
+
+/// The object store type.
+pub type Store = web::Data<dyn ObjectStore>;
+
+/// A blob in an [`ObjectStore`].
+#[derive(Clone, Debug)]
+pub struct Blob {
+    /// The hash of the blob.
+    pub hash: String,
+    /// The size of the blob in bytes.
+    pub len: u64,
+    /// When the blob was last written.
+    pub modified: SystemTime,
+}
+
+/// A content-addressed blob store.
+pub trait ObjectStore: Send + Sync {
+    /// Writes a blob under its hash.
+    ///
+    /// Writing a blob that already exists only updates when it was last
+    /// written, see [`Blob::modified`].
+    fn put<'a>(&'a self, hash: &'a str, data: Bytes) -> BoxFuture<'a, io::Result<()>>;
+
+    /// Gets the size of a blob in bytes, or `None` if it does not exist.
+    fn len<'a>(&'a self, hash: &'a str) -> BoxFuture<'a, io::Result<Option<u64>>>;
+
+    /// Reads a blob, or a range of bytes of it.
+    ///
+    /// Fails with [`io::ErrorKind::NotFound`] if the blob does not exist.
+    fn get<'a>(
+        &'a self,
+        hash: &'a str,
+        range: Option<Range<u64>>,
+    ) -> BoxFuture<'a, io::Result<Bytes>>;
+
+    /// Lists every blob in the store.
+    fn list(&self) -> BoxFuture<'_, io::Result<Vec<Blob>>>;
+
+    /// Deletes a blob. Deleting a blob that does not exist does nothing.
+    fn delete<'a>(&'a self, hash: &'a str) -> BoxFuture<'a, io::Result<()>>;
+}
+
+/// Hashes the contents of a blob, returning it in lowercase hex.
+pub fn hash(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// Checks if a string is a hash as returned by [`hash`].
+pub fn is_hash(s: &str) -> bool {
+    s.len() == 64 && s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}